@@ -0,0 +1,293 @@
+use std::sync::Arc;
+
+use futures_cpupool::{CpuFuture, CpuPool};
+
+use client::Client;
+use error::FleetError;
+use schema::{
+    Machine,
+    MachinePage,
+    Unit,
+    UnitOption,
+    UnitPage,
+    UnitState,
+    UnitStatePage,
+    UnitStates
+};
+
+/// A future resolving to the result of a fleet API call made by `AsyncClient`.
+pub type FleetFuture<T> = CpuFuture<T, FleetError>;
+
+/// An asynchronous API client for fleet.
+///
+/// `AsyncClient` offers the same operations as `Client`, but returns futures instead of blocking
+/// the calling thread. Requests are made by a `Client` on a pool of worker threads, so requests
+/// and responses are built and decoded exactly as they are by `Client`, and each future resolves
+/// to the same value or `FleetError` the corresponding `Client` method would return.
+///
+/// Calls do not wait on each other. Any number of requests, including requests for different
+/// pages or different collections, can be in flight at once, up to the number of threads in the
+/// pool. `AsyncClient` is cheap to clone, and clones share the same `Client` and thread pool.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate fleet;
+/// extern crate futures;
+///
+/// use fleet::AsyncClient;
+/// use futures::Future;
+///
+/// # fn main() {
+/// let client = AsyncClient::new("http://localhost:2999").ok().unwrap();
+///
+/// // Fetch units and machines at the same time.
+/// let units = client.units();
+/// let machines = client.machines();
+///
+/// let (units, machines) = units.join(machines).wait().ok().unwrap();
+///
+/// println!("{} units on {} machines", units.len(), machines.len());
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct AsyncClient {
+    client: Arc<Client>,
+    pool: CpuPool,
+}
+
+impl AsyncClient {
+    /// Constructs a new `AsyncClient`, using a thread pool with one thread per CPU.
+    ///
+    /// `root_url` is interpreted in the same way as in `Client::new`.
+    ///
+    /// # Failures
+    ///
+    /// If the value provided for `root_url` cannot be parsed, a `FleetError::Url` will be
+    /// returned.
+    pub fn new(root_url: &str) -> Result<AsyncClient, FleetError> {
+        let client = try!(Client::new(root_url));
+
+        Ok(AsyncClient::from_client(client, CpuPool::new_num_cpus()))
+    }
+
+    /// Constructs a new `AsyncClient` that makes requests with `client` on the threads of `pool`.
+    ///
+    /// This allows an `AsyncClient` to use any transport `Client` supports, and to share a thread
+    /// pool with the rest of an application.
+    pub fn from_client(client: Client, pool: CpuPool) -> AsyncClient {
+        AsyncClient {
+            client: Arc::new(client),
+            pool: pool,
+        }
+    }
+
+    /// Creates a new unit. See `Client::create_unit`.
+    pub fn create_unit(
+        &self,
+        name: &str,
+        desired_state: UnitStates,
+        options: Vec<UnitOption>
+    ) -> FleetFuture<()> {
+        let name = name.to_string();
+
+        self.spawn(move |client| client.create_unit(&name, desired_state, options))
+    }
+
+    /// Destroys the unit with the given name. See `Client::destroy_unit`.
+    pub fn destroy_unit(&self, name: &str) -> FleetFuture<()> {
+        let name = name.to_string();
+
+        self.spawn(move |client| client.destroy_unit(&name))
+    }
+
+    /// Gets a single unit by name. See `Client::get_unit`.
+    pub fn get_unit(&self, name: &str) -> FleetFuture<Unit> {
+        let name = name.to_string();
+
+        self.spawn(move |client| client.get_unit(&name))
+    }
+
+    /// Lists a page of machines in the fleet cluster. See `Client::list_machines`.
+    pub fn list_machines(&self, next_page_token: Option<String>) -> FleetFuture<MachinePage> {
+        self.spawn(move |client| client.list_machines(next_page_token))
+    }
+
+    /// Lists a page of the states of units in the fleet cluster. See `Client::list_unit_states`.
+    pub fn list_unit_states(
+        &self,
+        machine_id: Option<&str>,
+        unit_name: Option<&str>,
+        next_page_token: Option<String>,
+    ) -> FleetFuture<UnitStatePage> {
+        let machine_id = machine_id.map(|machine_id| machine_id.to_string());
+        let unit_name = unit_name.map(|unit_name| unit_name.to_string());
+
+        self.spawn(move |client| {
+            client.list_unit_states(
+                machine_id.as_ref().map(|machine_id| &machine_id[..]),
+                unit_name.as_ref().map(|unit_name| &unit_name[..]),
+                next_page_token
+            )
+        })
+    }
+
+    /// Lists a page of units in the fleet cluster. See `Client::list_units`.
+    pub fn list_units(&self, next_page_token: Option<String>) -> FleetFuture<UnitPage> {
+        self.spawn(move |client| client.list_units(next_page_token))
+    }
+
+    /// Returns every machine in the fleet cluster, requesting each page in turn. The future fails
+    /// with the first error encountered.
+    pub fn machines(&self) -> FleetFuture<Vec<Machine>> {
+        self.spawn(|client| client.machines().collect())
+    }
+
+    /// Modifies the desired state of an existing unit. See `Client::modify_unit`.
+    pub fn modify_unit(&self, name: &str, desired_state: UnitStates) -> FleetFuture<()> {
+        let name = name.to_string();
+
+        self.spawn(move |client| client.modify_unit(&name, desired_state))
+    }
+
+    /// Returns the states of every unit in the fleet cluster, requesting each page in turn.
+    /// `machine_id` and `unit_name` filter the unit states in the same way as in
+    /// `Client::list_unit_states`. The future fails with the first error encountered.
+    pub fn unit_states(
+        &self,
+        machine_id: Option<&str>,
+        unit_name: Option<&str>,
+    ) -> FleetFuture<Vec<UnitState>> {
+        let machine_id = machine_id.map(|machine_id| machine_id.to_string());
+        let unit_name = unit_name.map(|unit_name| unit_name.to_string());
+
+        self.spawn(move |client| {
+            client.unit_states(
+                machine_id.as_ref().map(|machine_id| &machine_id[..]),
+                unit_name.as_ref().map(|unit_name| &unit_name[..])
+            ).collect()
+        })
+    }
+
+    /// Returns every unit in the fleet cluster, requesting each page in turn. The future fails
+    /// with the first error encountered.
+    pub fn units(&self) -> FleetFuture<Vec<Unit>> {
+        self.spawn(|client| client.units().collect())
+    }
+
+    // Private
+
+    fn spawn<F, T>(&self, f: F) -> FleetFuture<T>
+    where F: FnOnce(&Client) -> Result<T, FleetError> + Send + 'static, T: Send + 'static {
+        let client = self.client.clone();
+
+        self.pool.spawn_fn(move || f(&client))
+    }
+}
+
+#[cfg(test)]
+mod async_client_tests {
+    use std::sync::Barrier;
+    use std::thread;
+    #[cfg(feature = "fake")]
+    use std::time::{Duration, Instant};
+
+    use futures::Future;
+    use futures_cpupool::CpuPool;
+
+    use super::AsyncClient;
+    use client::Client;
+    use error::FleetError;
+    #[cfg(feature = "fake")]
+    use fake::FakeFleet;
+    use transport::{Request, Response, Transport};
+
+    // Answers every request with an empty page, but only once as many requests as the barrier
+    // expects are being handled at the same time.
+    struct BarrierTransport {
+        barrier: Barrier,
+    }
+
+    impl Transport for BarrierTransport {
+        fn send(&self, _: Request) -> Result<Response, FleetError> {
+            self.barrier.wait();
+
+            Ok(Response {
+                status: 200,
+                body: "{}".to_string(),
+            })
+        }
+    }
+
+    fn client(parties: usize) -> AsyncClient {
+        let transport = BarrierTransport {
+            barrier: Barrier::new(parties),
+        };
+
+        let client = Client::with_transport("http://localhost", transport).ok().unwrap();
+
+        AsyncClient::from_client(client, CpuPool::new(parties))
+    }
+
+    #[test]
+    fn it_resolves_to_the_clients_result() {
+        let client = client(1);
+
+        let page = client.list_units(None).wait().ok().unwrap();
+
+        assert!(page.units.is_empty());
+        assert!(page.next_page_token.is_none());
+    }
+
+    #[test]
+    fn it_fetches_pages_concurrently() {
+        let client = client(3);
+
+        let units = client.list_units(Some("page-2".to_string()));
+        let machines = client.list_machines(None);
+        let states = client.list_unit_states(Some("abc123"), None, None);
+
+        // Each request blocks until all three are in flight, so this only completes if they are
+        // made concurrently.
+        let ((unit_page, machine_page), state_page) = units.join(machines).join(states)
+            .wait()
+            .ok()
+            .unwrap();
+
+        assert!(unit_page.units.is_empty());
+        assert!(machine_page.machines.is_empty());
+        assert!(state_page.states.is_empty());
+    }
+
+    #[cfg(feature = "fake")]
+    #[test]
+    fn it_sends_requests_concurrently_over_http() {
+        let fake = FakeFleet::start().unwrap();
+        fake.set_response_delay(Duration::from_millis(500));
+
+        let client = AsyncClient::from_client(fake.client(), CpuPool::new(3));
+        let started = Instant::now();
+
+        let units = client.list_units(None);
+        let machines = client.list_machines(None);
+        let states = client.list_unit_states(None, None, None);
+
+        units.join(machines).join(states).wait().ok().unwrap();
+
+        // The requests take 1.5 seconds in total if the client waits for each response before
+        // sending the next request.
+        assert!(started.elapsed() < Duration::from_millis(1200));
+    }
+
+    #[test]
+    fn it_can_be_shared_between_threads() {
+        let client = client(2);
+        let other_client = client.clone();
+
+        let thread = thread::spawn(move || other_client.units().wait().ok().unwrap());
+        let machines = client.machines().wait().ok().unwrap();
+
+        assert!(machines.is_empty());
+        assert!(thread.join().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,527 @@
+//! `fleet`, a command-line client for fleet in the style of `fleetctl`.
+//!
+//! Run `fleet --help` for usage. The exit status is 0 on success, 1 if a command fails and 2 if
+//! the command line is invalid.
+
+extern crate fleet;
+extern crate getopts;
+
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process;
+use std::time::Duration;
+
+use fleet::{parse_unit_file, ApiErrorKind, Client, ClientBuilder, UnitOption, UnitStates};
+use getopts::{Matches, Options};
+
+const DEFAULT_ENDPOINT: &'static str = "unix:///var/run/fleet.sock";
+
+const COMMANDS: &'static str = "Commands:
+    list-units              List the units in the cluster and their systemd states
+    list-unit-files         List the units in the cluster and their fleet states
+    list-machines           List the machines in the cluster
+    submit UNIT_FILE...     Submit units without scheduling them
+    load UNIT...            Schedule units to machines without starting them
+    start UNIT...           Schedule units to machines and start them
+    stop UNIT...            Stop units, leaving them scheduled
+    unload UNIT...          Unschedule units from their machines
+    destroy UNIT...         Destroy units
+    cat UNIT                Print the unit file of a unit
+    status UNIT...          Print the systemd states of units
+
+load and start also accept unit files, which are submitted first if the unit does not exist.";
+
+type CommandResult = Result<(), Box<Error>>;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let options = options();
+
+    let matches = match options.parse(&args[1..]) {
+        Ok(matches) => matches,
+        Err(error) => usage_error(&options, &error.to_string()),
+    };
+
+    if matches.opt_present("help") {
+        println!("{}", usage(&options));
+        return;
+    }
+
+    let (command, arguments) = match matches.free.split_first() {
+        Some((command, arguments)) => (command.clone(), arguments.to_vec()),
+        None => usage_error(&options, "No command given"),
+    };
+
+    let expected_arguments = match &command[..] {
+        "list-units" | "list-unit-files" | "list-machines" => Some(0),
+        "cat" => Some(1),
+        "submit" | "load" | "start" | "stop" | "unload" | "destroy" | "status" => None,
+        _ => usage_error(&options, &format!("Unknown command {}", command)),
+    };
+
+    match expected_arguments {
+        Some(0) if !arguments.is_empty() => {
+            usage_error(&options, &format!("{} takes no arguments", command))
+        },
+        Some(1) if arguments.len() != 1 => {
+            usage_error(&options, &format!("{} takes exactly one argument", command))
+        },
+        None if arguments.is_empty() => {
+            usage_error(&options, &format!("{} takes at least one argument", command))
+        },
+        _ => {},
+    }
+
+    let client = match build_client(&matches) {
+        Ok(client) => client,
+        Err(error) => fail(&*error),
+    };
+    let full = matches.opt_present("full");
+    let legend = !matches.opt_present("no-legend");
+
+    let result = match &command[..] {
+        "list-units" => list_units(&client, full, legend),
+        "list-unit-files" => list_unit_files(&client, full, legend),
+        "list-machines" => list_machines(&client, full, legend),
+        "submit" => submit(&client, &arguments),
+        "load" => set_state(&client, &arguments, UnitStates::Loaded, "load"),
+        "start" => set_state(&client, &arguments, UnitStates::Launched, "start"),
+        "stop" => set_state(&client, &arguments, UnitStates::Loaded, "stop"),
+        "unload" => set_state(&client, &arguments, UnitStates::Inactive, "unload"),
+        "destroy" => destroy(&client, &arguments),
+        "cat" => cat(&client, &arguments[0]),
+        "status" => status(&client, &arguments),
+        _ => unreachable!(),
+    };
+
+    if let Err(error) = result {
+        fail(&*error);
+    }
+}
+
+fn options() -> Options {
+    let mut options = Options::new();
+
+    options.optmulti(
+        "",
+        "endpoint",
+        "fleet API endpoint, or several separated by commas (default: FLEETCTL_ENDPOINT or \
+         unix:///var/run/fleet.sock)",
+        "URL"
+    );
+    options.optopt("", "ca-file", "certificate authority file for TLS", "FILE");
+    options.optopt("", "cert-file", "client certificate file for TLS", "FILE");
+    options.optopt("", "key-file", "client key file for TLS", "FILE");
+    options.optopt("", "request-timeout", "maximum seconds a request may take", "SECONDS");
+    options.optflag("l", "full", "do not shorten machine IDs and hashes");
+    options.optflag("", "no-legend", "do not print a header line in lists");
+    options.optflag("h", "help", "print this help");
+
+    options
+}
+
+fn usage(options: &Options) -> String {
+    format!("{}\n{}", options.usage("Usage: fleet [options] COMMAND [ARGUMENTS...]"), COMMANDS)
+}
+
+fn usage_error(options: &Options, message: &str) -> ! {
+    let _ = writeln!(io::stderr(), "Error: {}\n\n{}", message, usage(options));
+
+    process::exit(2);
+}
+
+fn fail(error: &Error) -> ! {
+    let _ = writeln!(io::stderr(), "Error: {}", error);
+
+    process::exit(1);
+}
+
+// Returns the endpoints given with `--endpoint`, or else in `FLEETCTL_ENDPOINT`, or else the
+// default. Each value may list several endpoints separated by commas.
+fn endpoints(flags: &[String], variable: Option<String>) -> Result<Vec<String>, String> {
+    let mut endpoints = split_endpoints(flags);
+
+    if endpoints.is_empty() {
+        endpoints = split_endpoints(&[variable.unwrap_or(DEFAULT_ENDPOINT.to_string())]);
+    }
+
+    if endpoints.is_empty() {
+        return Err("FLEETCTL_ENDPOINT does not contain any endpoints".to_string());
+    }
+
+    if endpoints.len() > 1 && endpoints.iter().any(|url| url.starts_with("unix://")) {
+        return Err("unix:// endpoints cannot be combined with other endpoints".to_string());
+    }
+
+    Ok(endpoints)
+}
+
+fn split_endpoints(values: &[String]) -> Vec<String> {
+    values.iter().flat_map(|value| {
+        value.split(',').map(|url| url.trim().to_string()).collect::<Vec<String>>()
+    }).filter(|url| !url.is_empty()).collect()
+}
+
+fn build_client(matches: &Matches) -> Result<Client, Box<Error>> {
+    let variable = env::var("FLEETCTL_ENDPOINT").ok();
+    let endpoints = try!(endpoints(&matches.opt_strs("endpoint"), variable));
+
+    let mut builder = if endpoints[0].starts_with("unix://") {
+        try!(unix_socket_builder(&endpoints[0]["unix://".len()..]))
+    } else {
+        let mut builder = ClientBuilder::new(&endpoints[0]);
+
+        for endpoint in endpoints[1..].iter() {
+            builder = builder.endpoint(endpoint);
+        }
+
+        builder
+    };
+
+    if let Some(path) = matches.opt_str("ca-file") {
+        builder = builder.ca_file(path);
+    }
+
+    match (matches.opt_str("cert-file"), matches.opt_str("key-file")) {
+        (Some(certificate), Some(key)) => builder = builder.client_certificate(certificate, key),
+        (None, None) => {},
+        _ => return Err(From::from("--cert-file and --key-file must be given together")),
+    }
+
+    if let Some(seconds) = matches.opt_str("request-timeout") {
+        let seconds: u64 = try!(seconds.parse().map_err(|_| {
+            format!("--request-timeout must be a whole number of seconds, not {}", seconds)
+        }));
+
+        builder = builder.request_timeout(Duration::from_secs(seconds));
+    }
+
+    Ok(try!(builder.build()))
+}
+
+#[cfg(unix)]
+fn unix_socket_builder(path: &str) -> Result<ClientBuilder, Box<Error>> {
+    Ok(ClientBuilder::new("http://localhost").unix_socket(path))
+}
+
+#[cfg(not(unix))]
+fn unix_socket_builder(_: &str) -> Result<ClientBuilder, Box<Error>> {
+    Err(From::from("unix:// endpoints are only supported on Unix"))
+}
+
+fn list_units(client: &Client, full: bool, legend: bool) -> CommandResult {
+    let machines = try!(machine_ips(client));
+    let mut rows = vec![];
+
+    for state in client.unit_states(None, None) {
+        let state = try!(state);
+
+        rows.push(vec![
+            state.name,
+            machine_label(state.machine_id.as_ref(), &machines, full),
+            state.systemd_active_state,
+            state.systemd_sub_state,
+        ]);
+    }
+
+    print_table(&["UNIT", "MACHINE", "ACTIVE", "SUB"], rows, legend);
+
+    Ok(())
+}
+
+fn list_unit_files(client: &Client, full: bool, legend: bool) -> CommandResult {
+    let machines = try!(machine_ips(client));
+    let mut rows = vec![];
+
+    for unit in client.units() {
+        let unit = try!(unit);
+        let hash = unit.hash();
+
+        rows.push(vec![
+            unit.name.clone(),
+            shorten(&hash, 7, full).to_string(),
+            unit.desired_state.to_string(),
+            unit.current_state.to_string(),
+            machine_label(unit.machine_id.as_ref(), &machines, full),
+        ]);
+    }
+
+    print_table(&["UNIT", "HASH", "DSTATE", "STATE", "TARGET"], rows, legend);
+
+    Ok(())
+}
+
+fn list_machines(client: &Client, full: bool, legend: bool) -> CommandResult {
+    let mut rows = vec![];
+
+    for machine in client.machines() {
+        let machine = try!(machine);
+        let mut metadata: Vec<String> = machine.metadata.iter().map(|(key, value)| {
+            format!("{}={}", key, value)
+        }).collect();
+
+        metadata.sort();
+
+        rows.push(vec![
+            shorten(&machine.id, 8, full).to_string(),
+            machine.primary_ip,
+            if metadata.is_empty() { "-".to_string() } else { metadata.join(",") },
+        ]);
+    }
+
+    print_table(&["MACHINE", "IP", "METADATA"], rows, legend);
+
+    Ok(())
+}
+
+fn submit(client: &Client, paths: &[String]) -> CommandResult {
+    for path in paths.iter() {
+        let (name, options) = try!(read_unit_file(path));
+
+        if try!(unit_exists(client, &name)) {
+            continue;
+        }
+
+        try!(client.create_unit(&name, UnitStates::Inactive, options));
+
+        println!("Unit {} submitted", name);
+    }
+
+    Ok(())
+}
+
+fn set_state(client: &Client, units: &[String], state: UnitStates, verb: &str) -> CommandResult {
+    for unit in units.iter() {
+        let name = if Path::new(unit).is_file() {
+            let (name, options) = try!(read_unit_file(unit));
+
+            if !try!(unit_exists(client, &name)) {
+                try!(client.create_unit(&name, state, options));
+                println!("Triggered unit {} {}", name, verb);
+
+                continue;
+            }
+
+            name
+        } else {
+            unit.clone()
+        };
+
+        try!(client.modify_unit(&name, state));
+
+        println!("Triggered unit {} {}", name, verb);
+    }
+
+    Ok(())
+}
+
+fn destroy(client: &Client, names: &[String]) -> CommandResult {
+    for name in names.iter() {
+        try!(client.destroy_unit(name));
+
+        println!("Destroyed {}", name);
+    }
+
+    Ok(())
+}
+
+fn cat(client: &Client, name: &str) -> CommandResult {
+    let unit = try!(client.get_unit(name));
+
+    print!("{}", unit.to_unit_file());
+
+    Ok(())
+}
+
+fn status(client: &Client, names: &[String]) -> CommandResult {
+    for name in names.iter() {
+        let unit = try!(client.get_unit(name));
+        let states: Vec<_> = try!(client.unit_states(None, Some(name)).collect());
+
+        println!("● {} - {} (desired {})", unit.name, unit.current_state, unit.desired_state);
+
+        if states.is_empty() {
+            println!("   Not running on any machine");
+        }
+
+        for state in states.iter() {
+            println!(
+                "   Machine: {}\n   Loaded: {}\n   Active: {} ({})",
+                state.machine_id.as_ref().map(|id| &id[..]).unwrap_or("-"),
+                state.systemd_load_state,
+                state.systemd_active_state,
+                state.systemd_sub_state
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the unit file at `path`, returning the unit name taken from the file name and the
+/// parsed options.
+fn read_unit_file(path: &str) -> Result<(String, Vec<UnitOption>), Box<Error>> {
+    let name = match Path::new(path).file_name().and_then(|name| name.to_str()) {
+        Some(name) => name.to_string(),
+        None => return Err(From::from(format!("{} is not a unit file", path))),
+    };
+
+    let mut contents = String::new();
+
+    try!(try!(File::open(path)).read_to_string(&mut contents));
+
+    let options = try!(parse_unit_file(&contents).map_err(|error| {
+        format!("{}: {}", path, error)
+    }));
+
+    Ok((name, options))
+}
+
+fn unit_exists(client: &Client, name: &str) -> Result<bool, Box<Error>> {
+    match client.get_unit(name) {
+        Ok(_) => Ok(true),
+        Err(ref error) if error.api_kind() == Some(ApiErrorKind::NotFound) => Ok(false),
+        Err(error) => Err(Box::new(error)),
+    }
+}
+
+fn machine_ips(client: &Client) -> Result<HashMap<String, String>, Box<Error>> {
+    let mut ips = HashMap::new();
+
+    for machine in client.machines() {
+        let machine = try!(machine);
+
+        ips.insert(machine.id, machine.primary_ip);
+    }
+
+    Ok(ips)
+}
+
+/// Formats a machine like `fleetctl` does, as its ID followed by its IP address.
+fn machine_label(id: Option<&String>, ips: &HashMap<String, String>, full: bool) -> String {
+    match id {
+        Some(id) => match ips.get(id) {
+            Some(ip) => format!("{}/{}", shorten(id, 8, full), ip),
+            None => shorten(id, 8, full).to_string(),
+        },
+        None => "-".to_string(),
+    }
+}
+
+fn shorten(value: &str, length: usize, full: bool) -> &str {
+    if full || value.len() <= length {
+        value
+    } else {
+        &value[..length]
+    }
+}
+
+fn print_table(header: &[&str], rows: Vec<Vec<String>>, legend: bool) {
+    print!("{}", format_table(header, rows, legend));
+}
+
+/// Lays out `rows` in columns separated by tabs, padded to line up, like `fleetctl` output.
+fn format_table(header: &[&str], rows: Vec<Vec<String>>, legend: bool) -> String {
+    let mut lines: Vec<Vec<String>> = vec![];
+
+    if legend {
+        lines.push(header.iter().map(|column| column.to_string()).collect());
+    }
+
+    lines.extend(rows);
+
+    let mut widths = vec![0; header.len()];
+
+    for line in lines.iter() {
+        for (index, column) in line.iter().enumerate() {
+            widths[index] = widths[index].max(column.chars().count());
+        }
+    }
+
+    let mut output = String::new();
+
+    for line in lines.iter() {
+        let last = line.len() - 1;
+
+        for (index, column) in line.iter().enumerate() {
+            output.push_str(column);
+
+            if index < last {
+                let padding = widths[index] - column.chars().count();
+
+                output.push_str(&" ".repeat(padding));
+                output.push('\t');
+            }
+        }
+
+        output.push('\n');
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod fleet_tests {
+    use std::collections::HashMap;
+
+    use super::{endpoints, format_table, machine_label};
+
+    #[test]
+    fn it_splits_endpoints_from_flags_and_the_environment() {
+        let flags = vec!["http://a:2999, http://b:2999".to_string(), "http://c:2999".to_string()];
+
+        assert_eq!(
+            endpoints(&flags, Some("http://d:2999".to_string())).unwrap(),
+            vec!["http://a:2999", "http://b:2999", "http://c:2999"]
+        );
+        assert_eq!(
+            endpoints(&[], Some("http://d:2999,http://e:2999".to_string())).unwrap(),
+            vec!["http://d:2999", "http://e:2999"]
+        );
+        assert_eq!(endpoints(&[], None).unwrap(), vec!["unix:///var/run/fleet.sock"]);
+        assert!(endpoints(&[], Some(",".to_string())).is_err());
+    }
+
+    #[test]
+    fn it_does_not_combine_unix_sockets_with_other_endpoints() {
+        let flags = vec!["unix:///var/run/fleet.sock,http://a:2999".to_string()];
+
+        assert_eq!(
+            endpoints(&flags, None).unwrap_err(),
+            "unix:// endpoints cannot be combined with other endpoints"
+        );
+    }
+
+    #[test]
+    fn it_lines_up_columns() {
+        let rows = vec![
+            vec!["web.service".to_string(), "active".to_string()],
+            vec!["db.service".to_string(), "failed".to_string()],
+        ];
+
+        assert_eq!(
+            format_table(&["UNIT", "ACTIVE"], rows.clone(), true),
+            "UNIT       \tACTIVE\nweb.service\tactive\ndb.service \tfailed\n"
+        );
+        assert_eq!(
+            format_table(&["UNIT", "ACTIVE"], rows, false),
+            "web.service\tactive\ndb.service \tfailed\n"
+        );
+    }
+
+    #[test]
+    fn it_labels_machines_with_their_ips() {
+        let id = "0123456789abcdef".to_string();
+        let mut ips = HashMap::new();
+
+        ips.insert(id.clone(), "10.0.0.1".to_string());
+
+        assert_eq!(machine_label(Some(&id), &ips, false), "01234567/10.0.0.1");
+        assert_eq!(machine_label(Some(&id), &ips, true), "0123456789abcdef/10.0.0.1");
+        assert_eq!(machine_label(None, &ips, false), "-");
+    }
+}
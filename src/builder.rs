@@ -0,0 +1,588 @@
+use std::fmt;
+use std::path::Path;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::time::Duration;
+
+use auth::{self, Credentials};
+use client::{self, Client};
+use connector::{Target, Timeouts};
+use error::FleetError;
+use pool::PoolConfig;
+use retry::RetryPolicy;
+use tls::TlsConfig;
+use transport::{self, Transport};
+
+/// A builder for a `Client` with custom settings.
+///
+/// `Client::new` and `Client::from_unix_socket` cover the common cases. `ClientBuilder` is needed
+/// to configure anything else, such as timeouts, retries, TLS or authentication.
+///
+/// # Timeouts
+///
+/// By default a request waits as long as it takes fleet to respond. When a timeout expires, the
+/// request fails with a `FleetError::Timeout` saying which timeout it was, and the connection is
+/// closed rather than returned to the connection pool.
+///
+/// * The *connect* timeout limits how long opening a new connection to fleet may take.
+/// * The *read* timeout limits how long fleet may go without sending any data while a response is
+///   being read.
+/// * The *write* timeout limits how long sending any part of the request may block.
+/// * The *request* timeout limits the time taken by the request as a whole, from connecting to
+///   reading the last byte of the response.
+///
+/// # Retries
+///
+/// By default, failed requests are not retried. A `RetryPolicy` set with
+/// `ClientBuilder::retry_policy` is applied to every request the `Client` makes, whichever
+/// transport it uses.
+///
+/// # TLS
+///
+/// When the root URL uses the https scheme, fleet's certificate is verified against the system's
+/// trusted certificate authorities and must be valid for the host in the URL. For fleet behind a
+/// TLS terminator that uses a private certificate authority or requires client certificates:
+///
+/// * `ca_file` adds the certificate authorities in a PEM file to the trusted ones.
+/// * `client_certificate` presents a certificate and private key, both PEM files, to fleet.
+/// * `tls_server_name` sends a different server name than the host in the URL, which fleet's
+///   certificate must then be valid for instead. This is useful when connecting by IP address.
+/// * `verify_hostname(false)` accepts any certificate signed by a trusted certificate authority,
+///   whatever name it was issued for.
+///
+/// The files are loaded by `build`, which fails with a `FleetError::Tls` if any of them is
+/// invalid.
+///
+/// # Failover
+///
+/// Additional endpoints added with `endpoint` are tried in order when a request fails at the
+/// ones before it. An endpoint that failed is skipped for the time set with `endpoint_cooldown`.
+/// See "Failover" in the documentation of `Client`.
+///
+/// # Authentication
+///
+/// `basic_auth` and `bearer_auth` set the credentials sent in the `Authorization` header of every
+/// request, taking precedence over any user name and password in the root URL. Any other headers
+/// that every request should carry can be added with `header`. Credentials are never shown in the
+/// `Debug` output of `ClientBuilder` or `Client`.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use fleet::ClientBuilder;
+///
+/// let client = ClientBuilder::new("http://localhost:2999")
+///     .connect_timeout(Duration::from_secs(1))
+///     .read_timeout(Duration::from_secs(5))
+///     .request_timeout(Duration::from_secs(10))
+///     .build()
+///     .ok()
+///     .unwrap();
+/// ```
+pub struct ClientBuilder {
+    root_urls: Vec<String>,
+    cooldown: Duration,
+    credentials: Option<Credentials>,
+    headers: Vec<(String, String)>,
+    pool_config: PoolConfig,
+    retry_policy: RetryPolicy,
+    timeouts: Timeouts,
+    tls: TlsConfig,
+    transport: Option<Box<Transport>>,
+    #[cfg(unix)]
+    unix_socket: Option<PathBuf>,
+}
+
+impl ClientBuilder {
+    /// Constructs a new `ClientBuilder` for a `Client` that talks to fleetd at `root_url`, which is
+    /// interpreted in the same way as in `Client::new`.
+    pub fn new(root_url: &str) -> ClientBuilder {
+        ClientBuilder {
+            root_urls: vec![root_url.to_string()],
+            cooldown: Duration::from_secs(30),
+            credentials: None,
+            headers: vec![],
+            pool_config: PoolConfig::default(),
+            retry_policy: RetryPolicy::never(),
+            timeouts: Timeouts::default(),
+            tls: TlsConfig::default(),
+            transport: None,
+            #[cfg(unix)]
+            unix_socket: None,
+        }
+    }
+
+    /// Sets the configuration of the connection pool. See `Client::with_pool_config`.
+    pub fn pool_config(mut self, config: PoolConfig) -> ClientBuilder {
+        self.pool_config = config;
+        self
+    }
+
+    /// Adds another fleet endpoint at `root_url`, which is interpreted in the same way as in
+    /// `Client::new`. Requests that fail at the endpoints added before it move on to this one.
+    /// See "Failover" in the documentation of `Client`.
+    pub fn endpoint(mut self, root_url: &str) -> ClientBuilder {
+        self.root_urls.push(root_url.to_string());
+        self
+    }
+
+    /// Sets how long an endpoint that failed is skipped by later requests. Defaults to 30
+    /// seconds.
+    pub fn endpoint_cooldown(mut self, cooldown: Duration) -> ClientBuilder {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Sends the header `name` with the value `value` with every request. Setting the same header
+    /// again replaces its value.
+    pub fn header(mut self, name: &str, value: &str) -> ClientBuilder {
+        self.headers.retain(|&(ref existing, _)| existing.to_lowercase() != name.to_lowercase());
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Authenticates every request with HTTP basic authentication.
+    pub fn basic_auth(mut self, username: &str, password: Option<&str>) -> ClientBuilder {
+        self.credentials = Some(Credentials::Basic {
+            username: username.to_string(),
+            password: password.map(|password| password.to_string()),
+        });
+        self
+    }
+
+    /// Authenticates every request with a bearer token.
+    pub fn bearer_auth(mut self, token: &str) -> ClientBuilder {
+        self.credentials = Some(Credentials::Bearer(token.to_string()));
+        self
+    }
+
+    /// Sets the policy for retrying failed requests.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> ClientBuilder {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Sends requests through `transport` instead of the default `HyperTransport`. When a
+    /// transport is given, the connection pool, Unix socket and timeout settings are ignored.
+    pub fn transport<T: Transport + 'static>(mut self, transport: T) -> ClientBuilder {
+        self.transport = Some(Box::new(transport));
+        self
+    }
+
+    /// Connects to fleetd over the Unix domain socket at `path` instead of over TCP. The host in
+    /// the root URL is still sent to fleet, but is not used to connect. See
+    /// `Client::from_unix_socket`.
+    #[cfg(unix)]
+    pub fn unix_socket<P: AsRef<Path>>(mut self, path: P) -> ClientBuilder {
+        self.unix_socket = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the maximum time opening a connection to fleet may take.
+    ///
+    /// This timeout, and the part of the request timeout spent connecting, does not apply to
+    /// connections over a Unix domain socket, which the standard library cannot open with a
+    /// timeout. Connecting to a socket file only blocks while fleetd's queue of connections
+    /// waiting to be accepted is full. Reads and writes on the socket are still limited.
+    pub fn connect_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.timeouts.connect = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum time to wait for fleet to send data while reading a response.
+    pub fn read_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.timeouts.read = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum time sending any part of a request may block.
+    pub fn write_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.timeouts.write = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum time a request may take as a whole. Over a Unix domain socket, the time
+    /// spent opening the connection is not limited; see `connect_timeout`.
+    pub fn request_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.timeouts.request = Some(timeout);
+        self
+    }
+
+    /// Trusts the certificate authorities in the PEM file at `path`, in addition to the system's.
+    pub fn ca_file<P: AsRef<Path>>(mut self, path: P) -> ClientBuilder {
+        self.tls.ca_file = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Presents the certificate in the PEM file at `certificate`, whose private key is in the PEM
+    /// file at `key`, to fleet when connecting over HTTPS.
+    pub fn client_certificate<P, Q>(mut self, certificate: P, key: Q) -> ClientBuilder
+    where P: AsRef<Path>, Q: AsRef<Path> {
+        self.tls.client_certificate = Some((
+            certificate.as_ref().to_path_buf(),
+            key.as_ref().to_path_buf(),
+        ));
+        self
+    }
+
+    /// Sets the server name sent to fleet during the TLS handshake, and which fleet's certificate
+    /// is verified against, in place of the host in the root URL.
+    pub fn tls_server_name(mut self, server_name: &str) -> ClientBuilder {
+        self.tls.server_name = Some(server_name.to_string());
+        self
+    }
+
+    /// Sets whether fleet's certificate must be valid for the host it is reached at. Enabled by
+    /// default. Disabling this still requires a certificate signed by a trusted authority.
+    pub fn verify_hostname(mut self, verify: bool) -> ClientBuilder {
+        self.tls.verify_hostname = verify;
+        self
+    }
+
+    /// Constructs the `Client`.
+    ///
+    /// # Failures
+    ///
+    /// If the root URL cannot be parsed, a `FleetError::Url` will be returned. If a file given
+    /// for TLS cannot be loaded, a `FleetError::Tls` will be returned.
+    pub fn build(self) -> Result<Client, FleetError> {
+        let transport = match self.transport {
+            Some(transport) => transport,
+            None => {
+                #[cfg(unix)]
+                let target = match self.unix_socket {
+                    Some(path) => Target::Unix(path),
+                    None => Target::Tcp(Some(try!(self.tls.connector()))),
+                };
+                #[cfg(not(unix))]
+                let target = Target::Tcp(Some(try!(self.tls.connector())));
+
+                Box::new(transport::with_target(self.pool_config, target, self.timeouts))
+            },
+        };
+
+        client::from_parts(
+            &self.root_urls,
+            transport,
+            self.retry_policy,
+            self.headers,
+            self.credentials,
+            self.cooldown
+        )
+    }
+}
+
+impl fmt::Debug for ClientBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut debug = f.debug_struct("ClientBuilder");
+
+        debug
+            .field("root_urls", &self.root_urls.iter().map(|url| auth::redact_url(url))
+                .collect::<Vec<String>>())
+            .field("credentials", &self.credentials)
+            .field("headers", &auth::redact_headers(&self.headers))
+            .field("retry_policy", &self.retry_policy)
+            .field("tls", &self.tls);
+
+        #[cfg(unix)]
+        debug.field("unix_socket", &self.unix_socket);
+
+        debug.finish()
+    }
+}
+
+#[cfg(test)]
+mod client_builder_tests {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use openssl::ssl::{SslContext, SslMethod, SslStream};
+    use openssl::ssl::{SSL_VERIFY_FAIL_IF_NO_PEER_CERT, SSL_VERIFY_PEER};
+    use openssl::x509::X509FileType;
+
+    use super::ClientBuilder;
+    use error::{FleetError, TimeoutKind};
+
+    // Starts a server that accepts `connections` connections but never responds, returning its
+    // URL. Connections are held open until the sender of the returned channel is dropped.
+    fn unresponsive_server(connections: usize) -> (String, mpsc::Sender<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            let streams: Vec<TcpStream> = listener.incoming().take(connections)
+                .map(|stream| stream.unwrap())
+                .collect();
+
+            let _ = receiver.recv();
+
+            drop(streams);
+        });
+
+        (url, sender)
+    }
+
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/fixtures/tls/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    // Starts a TLS server with a certificate for localhost, fleet.test and 127.0.0.1, signed by
+    // the test CA, that answers one request with an empty page. Returns its port and a channel
+    // that receives the server name the client sent. If `require_client_certificate` is set, the
+    // client must present a certificate signed by the test CA.
+    fn tls_server(require_client_certificate: bool) -> (u16, mpsc::Receiver<Option<String>>) {
+        let mut context = SslContext::new(SslMethod::Sslv23).unwrap();
+
+        context.set_certificate_chain_file(fixture("server.pem"), X509FileType::PEM).unwrap();
+        context.set_private_key_file(fixture("server.key"), X509FileType::PEM).unwrap();
+
+        if require_client_certificate {
+            context.set_CA_file(fixture("ca.pem")).unwrap();
+            context.set_verify(SSL_VERIFY_PEER | SSL_VERIFY_FAIL_IF_NO_PEER_CERT, None);
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+
+            let mut stream = match SslStream::new_server(&context, stream) {
+                Ok(stream) => stream,
+                Err(_) => return,
+            };
+
+            let server_name = stream.ssl().get_servername();
+
+            let mut request = vec![];
+            let mut buffer = [0; 1024];
+
+            while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                match stream.read(&mut buffer) {
+                    Ok(0) | Err(_) => return,
+                    Ok(read) => request.extend_from_slice(&buffer[..read]),
+                }
+            }
+
+            stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}").unwrap();
+
+            let _ = sender.send(server_name);
+        });
+
+        (port, receiver)
+    }
+
+    fn tls_error(error: FleetError) -> String {
+        match error {
+            FleetError::Tls(error) => error.message,
+            error => panic!("expected a TLS error, got: {}", error),
+        }
+    }
+
+    fn timeout_kind(error: FleetError) -> TimeoutKind {
+        match error {
+            FleetError::Timeout(kind) => kind,
+            error => panic!("expected a timeout, got: {}", error),
+        }
+    }
+
+    #[test]
+    fn it_builds_a_client() {
+        assert!(ClientBuilder::new("http://localhost:2999").build().is_ok());
+        assert!(ClientBuilder::new("asdf").build().is_err());
+    }
+
+    #[test]
+    fn it_times_out_waiting_for_a_response() {
+        let (url, _server) = unresponsive_server(1);
+
+        let client = ClientBuilder::new(&url)
+            .read_timeout(Duration::from_millis(100))
+            .build()
+            .ok()
+            .unwrap();
+
+        let started = Instant::now();
+        let error = client.list_machines(None).err().unwrap();
+
+        assert_eq!(timeout_kind(error), TimeoutKind::Read);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn it_times_out_the_whole_request() {
+        let (url, _server) = unresponsive_server(1);
+
+        let client = ClientBuilder::new(&url)
+            .read_timeout(Duration::from_secs(10))
+            .request_timeout(Duration::from_millis(100))
+            .build()
+            .ok()
+            .unwrap();
+
+        let started = Instant::now();
+        let error = client.get_unit("test.service").err().unwrap();
+
+        assert_eq!(timeout_kind(error), TimeoutKind::Request);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn it_times_out_concurrent_requests_separately() {
+        let (url, _server) = unresponsive_server(2);
+
+        let client = Arc::new(ClientBuilder::new(&url)
+            .request_timeout(Duration::from_millis(500))
+            .build()
+            .ok()
+            .unwrap());
+
+        let started = Instant::now();
+
+        let threads: Vec<_> = (0..2).map(|_| {
+            let client = client.clone();
+
+            thread::spawn(move || timeout_kind(client.list_machines(None).err().unwrap()))
+        }).collect();
+
+        for thread in threads {
+            assert_eq!(thread.join().unwrap(), TimeoutKind::Request);
+        }
+
+        // Both requests wait for a response at the same time rather than one after the other.
+        assert!(started.elapsed() < Duration::from_millis(900));
+    }
+
+    #[test]
+    fn it_connects_to_fleet_with_mutual_tls() {
+        let (port, server_names) = tls_server(true);
+
+        let client = ClientBuilder::new(&format!("https://localhost:{}", port))
+            .ca_file(fixture("ca.pem"))
+            .client_certificate(fixture("client.pem"), fixture("client.key"))
+            .build()
+            .ok()
+            .unwrap();
+
+        assert!(client.list_machines(None).ok().unwrap().machines.is_empty());
+        assert_eq!(server_names.recv().unwrap(), Some("localhost".to_string()));
+    }
+
+    #[test]
+    fn it_rejects_certificates_from_untrusted_authorities() {
+        let (port, _) = tls_server(false);
+
+        let client = ClientBuilder::new(&format!("https://localhost:{}", port))
+            .build()
+            .ok()
+            .unwrap();
+
+        let message = tls_error(client.list_machines(None).err().unwrap());
+
+        assert!(message.starts_with("TLS handshake with `localhost` failed"));
+    }
+
+    #[test]
+    fn it_fails_without_a_client_certificate_when_fleet_requires_one() {
+        let (port, _) = tls_server(true);
+
+        let client = ClientBuilder::new(&format!("https://localhost:{}", port))
+            .ca_file(fixture("ca.pem"))
+            .build()
+            .ok()
+            .unwrap();
+
+        assert!(client.list_machines(None).is_err());
+    }
+
+    #[test]
+    fn it_overrides_the_tls_server_name() {
+        let (port, server_names) = tls_server(false);
+
+        let client = ClientBuilder::new(&format!("https://127.0.0.1:{}", port))
+            .ca_file(fixture("ca.pem"))
+            .tls_server_name("fleet.test")
+            .build()
+            .ok()
+            .unwrap();
+
+        assert!(client.list_machines(None).is_ok());
+        assert_eq!(server_names.recv().unwrap(), Some("fleet.test".to_string()));
+    }
+
+    #[test]
+    fn it_verifies_ip_addresses_against_the_certificate() {
+        let (port, server_names) = tls_server(false);
+
+        let client = ClientBuilder::new(&format!("https://127.0.0.1:{}", port))
+            .ca_file(fixture("ca.pem"))
+            .build()
+            .ok()
+            .unwrap();
+
+        assert!(client.list_machines(None).is_ok());
+        assert_eq!(server_names.recv().unwrap(), None);
+
+        let (port, _) = tls_server(false);
+
+        let client = ClientBuilder::new(&format!("https://127.0.0.1:{}", port))
+            .ca_file(fixture("ca.pem"))
+            .tls_server_name("127.0.0.2")
+            .build()
+            .ok()
+            .unwrap();
+
+        let message = tls_error(client.list_machines(None).err().unwrap());
+
+        assert_eq!(
+            message,
+            "TLS handshake with `127.0.0.2` failed: the certificate is not valid for `127.0.0.2`"
+        );
+    }
+
+    #[test]
+    fn it_verifies_the_hostname_unless_disabled() {
+        let (port, _) = tls_server(false);
+
+        let client = ClientBuilder::new(&format!("https://127.0.0.1:{}", port))
+            .ca_file(fixture("ca.pem"))
+            .tls_server_name("other.test")
+            .build()
+            .ok()
+            .unwrap();
+
+        let message = tls_error(client.list_machines(None).err().unwrap());
+
+        assert!(message.starts_with("TLS handshake with `other.test` failed"));
+
+        let (port, _) = tls_server(false);
+
+        let client = ClientBuilder::new(&format!("https://127.0.0.1:{}", port))
+            .ca_file(fixture("ca.pem"))
+            .tls_server_name("other.test")
+            .verify_hostname(false)
+            .build()
+            .ok()
+            .unwrap();
+
+        assert!(client.list_machines(None).is_ok());
+    }
+
+    #[test]
+    fn it_fails_to_build_with_missing_tls_files() {
+        let error = ClientBuilder::new("https://localhost:2999")
+            .client_certificate(fixture("missing.pem"), fixture("client.key"))
+            .build()
+            .err()
+            .unwrap();
+
+        assert!(tls_error(error).starts_with("could not load client certificate"));
+    }
+}
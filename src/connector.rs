@@ -0,0 +1,344 @@
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use hyper::net::NetworkStream;
+use openssl::ssl::SslStream;
+
+use error::TimeoutKind;
+use pool::Reusable;
+use tls::{TlsConfig, TlsConnector};
+
+/// The timeouts applied to the requests a `Client` makes. `None` means no timeout.
+#[derive(Clone, Copy, Default)]
+pub struct Timeouts {
+    pub connect: Option<Duration>,
+    pub read: Option<Duration>,
+    pub write: Option<Duration>,
+    pub request: Option<Duration>,
+}
+
+/// The time by which a request must finish. Each request has its own, which is applied to the
+/// connection the request is sent on.
+#[derive(Clone, Copy, Default)]
+pub struct Deadline(Option<Instant>);
+
+impl Deadline {
+    /// Returns the deadline for a request starting now that may take at most `timeout`.
+    pub fn after(timeout: Option<Duration>) -> Deadline {
+        Deadline(timeout.map(|timeout| Instant::now() + timeout))
+    }
+
+    // Returns the timeout to use for a single operation limited by `timeout`, along with which
+    // timeout it represents, or an error if the deadline has already passed.
+    fn limit(
+        &self,
+        timeout: Option<Duration>,
+        kind: TimeoutKind,
+    ) -> io::Result<(Option<Duration>, TimeoutKind)> {
+        let remaining = match self.0 {
+            Some(deadline) => {
+                let now = Instant::now();
+
+                if deadline <= now {
+                    return Err(timed_out(TimeoutKind::Request));
+                }
+
+                Some(deadline - now)
+            },
+            None => None,
+        };
+
+        Ok(match (timeout, remaining) {
+            (Some(timeout), Some(remaining)) if remaining < timeout => {
+                (Some(remaining), TimeoutKind::Request)
+            },
+            (None, Some(remaining)) => (Some(remaining), TimeoutKind::Request),
+            (timeout, _) => (timeout, kind),
+        })
+    }
+}
+
+fn timed_out(kind: TimeoutKind) -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, kind)
+}
+
+// Sockets report an expired timeout as `WouldBlock` on Unix and `TimedOut` on Windows.
+fn check_timeout(error: io::Error, kind: TimeoutKind) -> io::Error {
+    match error.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => timed_out(kind),
+        _ => error,
+    }
+}
+
+/// Where `Connector` reaches fleetd.
+pub enum Target {
+    /// Over TCP, using TLS for https URLs. Without a `TlsConnector`, one with the default settings
+    /// is created when the first HTTPS connection is made.
+    Tcp(Option<TlsConnector>),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+// Where a `Connector` opens connections, once any `TlsConnector` has been taken out of its
+// `Target`.
+enum Route {
+    Tcp,
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+/// Opens the connections used by `HyperTransport`, which reach fleetd either over TCP or over a
+/// Unix domain socket and apply the configured timeouts. A single `Connector` is shared by every
+/// request, so connections can be opened for several requests at once.
+pub struct Connector {
+    route: Route,
+    tls: Mutex<Option<Arc<TlsConnector>>>,
+    timeouts: Timeouts,
+}
+
+impl Connector {
+    pub fn new(target: Target, timeouts: Timeouts) -> Connector {
+        let (route, tls) = match target {
+            Target::Tcp(tls) => (Route::Tcp, tls),
+            #[cfg(unix)]
+            Target::Unix(path) => (Route::Unix(path), None),
+        };
+
+        Connector {
+            route: route,
+            tls: Mutex::new(tls.map(Arc::new)),
+            timeouts: timeouts,
+        }
+    }
+
+    /// Opens a connection to `host` for a request that must finish by `deadline`.
+    pub fn connect(
+        &self,
+        host: &str,
+        port: u16,
+        scheme: &str,
+        deadline: Deadline,
+    ) -> io::Result<Stream> {
+        let inner = match self.route {
+            Route::Tcp if scheme == "https" => {
+                Inner::Tls(try!(self.connect_tls(host, port, deadline)))
+            },
+            Route::Tcp => Inner::Tcp(try!(self.connect_tcp(host, port, deadline))),
+            // The host and port of the URL are meaningless for a socket file, so every request
+            // goes to the same path. The standard library cannot connect to a socket file with a
+            // timeout, so only a deadline that has already passed is enforced here.
+            #[cfg(unix)]
+            Route::Unix(ref path) => {
+                try!(deadline.limit(self.timeouts.connect, TimeoutKind::Connect));
+
+                Inner::Unix(try!(UnixStream::connect(path)))
+            },
+        };
+
+        Ok(Stream {
+            inner: inner,
+            timeouts: self.timeouts,
+            deadline: deadline,
+        })
+    }
+
+    fn connect_tcp(&self, host: &str, port: u16, deadline: Deadline) -> io::Result<TcpStream> {
+        let mut last_error = io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "host did not resolve to any addresses",
+        );
+
+        for address in try!((host, port).to_socket_addrs()) {
+            let (timeout, kind) = try!(deadline.limit(self.timeouts.connect, TimeoutKind::Connect));
+
+            let result = match timeout {
+                Some(timeout) => TcpStream::connect_timeout(&address, timeout),
+                None => TcpStream::connect(address),
+            };
+
+            match result {
+                Ok(stream) => return Ok(stream),
+                Err(error) => last_error = check_timeout(error, kind),
+            }
+        }
+
+        Err(last_error)
+    }
+
+    fn connect_tls(
+        &self,
+        host: &str,
+        port: u16,
+        deadline: Deadline,
+    ) -> io::Result<SslStream<TcpStream>> {
+        let stream = try!(self.connect_tcp(host, port, deadline));
+
+        // The handshake is bounded by the read and write timeouts, like the request itself.
+        let (read_timeout, read_kind) = try!(deadline.limit(self.timeouts.read, TimeoutKind::Read));
+        let (write_timeout, _) = try!(deadline.limit(self.timeouts.write, TimeoutKind::Write));
+
+        try!(stream.set_read_timeout(read_timeout));
+        try!(stream.set_write_timeout(write_timeout));
+
+        try!(self.tls_connector())
+            .connect(host, stream)
+            .map_err(|error| check_timeout(error, read_kind))
+    }
+
+    // Returns the connector for the TLS handshake, creating one with the default settings the
+    // first time it is needed if none was configured.
+    fn tls_connector(&self) -> io::Result<Arc<TlsConnector>> {
+        let mut tls = self.tls.lock().unwrap();
+
+        if tls.is_none() {
+            *tls = Some(Arc::new(try!(TlsConfig::default().connector().map_err(|error| {
+                io::Error::new(io::ErrorKind::Other, error)
+            }))));
+        }
+
+        Ok(tls.as_ref().unwrap().clone())
+    }
+}
+
+enum Inner {
+    Tcp(TcpStream),
+    Tls(SslStream<TcpStream>),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+/// A connection opened by `Connector`.
+///
+/// Read and write timeouts are set on the socket before each operation, shortened as needed so
+/// the operation cannot outlast the deadline of the request using the connection.
+pub struct Stream {
+    inner: Inner,
+    timeouts: Timeouts,
+    deadline: Deadline,
+}
+
+impl Stream {
+    /// Applies the deadline of the request about to use this connection, which may have been
+    /// opened for an earlier one.
+    pub fn set_deadline(&mut self, deadline: Deadline) {
+        self.deadline = deadline;
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let (timeout, kind) = try!(self.deadline.limit(self.timeouts.read, TimeoutKind::Read));
+
+        let result = match self.inner {
+            Inner::Tcp(ref mut stream) => {
+                try!(stream.set_read_timeout(timeout));
+                stream.read(buf)
+            },
+            Inner::Tls(ref mut stream) => {
+                try!(stream.get_ref().set_read_timeout(timeout));
+                stream.read(buf)
+            },
+            #[cfg(unix)]
+            Inner::Unix(ref mut stream) => {
+                try!(stream.set_read_timeout(timeout));
+                stream.read(buf)
+            },
+        };
+
+        result.map_err(|error| check_timeout(error, kind))
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let (timeout, kind) = try!(self.deadline.limit(self.timeouts.write, TimeoutKind::Write));
+
+        let result = match self.inner {
+            Inner::Tcp(ref mut stream) => {
+                try!(stream.set_write_timeout(timeout));
+                stream.write(buf)
+            },
+            Inner::Tls(ref mut stream) => {
+                try!(stream.get_ref().set_write_timeout(timeout));
+                stream.write(buf)
+            },
+            #[cfg(unix)]
+            Inner::Unix(ref mut stream) => {
+                try!(stream.set_write_timeout(timeout));
+                stream.write(buf)
+            },
+        };
+
+        result.map_err(|error| check_timeout(error, kind))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.inner {
+            Inner::Tcp(ref mut stream) => stream.flush(),
+            Inner::Tls(ref mut stream) => stream.flush(),
+            #[cfg(unix)]
+            Inner::Unix(ref mut stream) => stream.flush(),
+        }
+    }
+}
+
+impl NetworkStream for Stream {
+    fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+        match self.inner {
+            Inner::Tcp(ref mut stream) => stream.peer_addr(),
+            Inner::Tls(ref mut stream) => stream.get_ref().peer_addr(),
+            #[cfg(unix)]
+            Inner::Unix(_) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "Unix domain sockets do not have a socket address",
+            )),
+        }
+    }
+}
+
+impl Reusable for Stream {
+    fn is_reusable(&mut self) -> bool {
+        match self.inner {
+            Inner::Tcp(ref stream) => tcp_is_idle(stream),
+            Inner::Tls(ref stream) => tcp_is_idle(stream.get_ref()),
+            #[cfg(unix)]
+            Inner::Unix(ref stream) => unix_is_idle(stream),
+        }
+    }
+}
+
+// A read that would block is the only sign that the peer has neither closed the connection nor
+// sent something no request asked for.
+fn would_block(result: io::Result<usize>) -> bool {
+    match result {
+        Ok(_) => false,
+        Err(error) => error.kind() == io::ErrorKind::WouldBlock,
+    }
+}
+
+fn tcp_is_idle(mut stream: &TcpStream) -> bool {
+    if stream.set_nonblocking(true).is_err() {
+        return false;
+    }
+
+    let idle = would_block(stream.read(&mut [0; 1]));
+
+    stream.set_nonblocking(false).is_ok() && idle
+}
+
+#[cfg(unix)]
+fn unix_is_idle(mut stream: &UnixStream) -> bool {
+    if stream.set_nonblocking(true).is_err() {
+        return false;
+    }
+
+    let idle = would_block(stream.read(&mut [0; 1]));
+
+    stream.set_nonblocking(false).is_ok() && idle
+}
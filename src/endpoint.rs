@@ -0,0 +1,144 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The fleet API endpoints a `Client` sends its requests to, and how healthy each one is.
+///
+/// Requests go to the preferred endpoint, which is the one that most recently answered, for as
+/// long as it keeps answering. An endpoint that fails is cooled down: it is skipped for the
+/// cooldown period unless every endpoint is cooling down.
+pub struct Endpoints {
+    roots: Vec<String>,
+    cooldown: Duration,
+    health: Mutex<Health>,
+}
+
+struct Health {
+    preferred: usize,
+    unhealthy_until: Vec<Option<Instant>>,
+}
+
+impl Endpoints {
+    /// Constructs a new `Endpoints` from root URLs ending in a slash, in order of preference.
+    pub fn new(roots: Vec<String>, cooldown: Duration) -> Endpoints {
+        let count = roots.len();
+
+        Endpoints {
+            roots: roots,
+            cooldown: cooldown,
+            health: Mutex::new(Health {
+                preferred: 0,
+                unhealthy_until: vec![None; count],
+            }),
+        }
+    }
+
+    /// Returns the number of endpoints.
+    pub fn len(&self) -> usize {
+        self.roots.len()
+    }
+
+    /// Returns the root URLs of the endpoints.
+    pub fn roots(&self) -> &[String] {
+        &self.roots[..]
+    }
+
+    /// Returns the root URL of the endpoint at `index`.
+    pub fn root(&self, index: usize) -> &str {
+        &self.roots[index][..]
+    }
+
+    /// Returns the indexes of the endpoints in the order a request should try them: healthy
+    /// endpoints first, starting with the preferred one, then those cooling down, starting with
+    /// the one that recovers soonest.
+    pub fn order(&self) -> Vec<usize> {
+        let health = self.health.lock().unwrap();
+        let now = Instant::now();
+        let count = self.roots.len();
+
+        let mut healthy = vec![];
+        let mut cooling_down = vec![];
+
+        for offset in 0..count {
+            let index = (health.preferred + offset) % count;
+
+            match health.unhealthy_until[index] {
+                Some(until) if until > now => cooling_down.push((until, index)),
+                _ => healthy.push(index),
+            }
+        }
+
+        cooling_down.sort();
+        healthy.extend(cooling_down.into_iter().map(|(_, index)| index));
+
+        healthy
+    }
+
+    /// Records that the endpoint at `index` answered, making it the preferred endpoint.
+    pub fn succeeded(&self, index: usize) {
+        let mut health = self.health.lock().unwrap();
+
+        health.preferred = index;
+        health.unhealthy_until[index] = None;
+    }
+
+    /// Records that the endpoint at `index` failed, cooling it down.
+    pub fn failed(&self, index: usize) {
+        let mut health = self.health.lock().unwrap();
+
+        health.unhealthy_until[index] = Some(Instant::now() + self.cooldown);
+    }
+}
+
+#[cfg(test)]
+mod endpoints_tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use super::Endpoints;
+
+    fn endpoints(cooldown: Duration) -> Endpoints {
+        Endpoints::new(vec![
+            "http://10.0.0.1:2999/".to_string(),
+            "http://10.0.0.2:2999/".to_string(),
+            "http://10.0.0.3:2999/".to_string(),
+        ], cooldown)
+    }
+
+    #[test]
+    fn it_prefers_the_endpoint_that_last_answered() {
+        let endpoints = endpoints(Duration::from_secs(30));
+
+        assert_eq!(endpoints.order(), vec![0, 1, 2]);
+
+        endpoints.succeeded(1);
+
+        assert_eq!(endpoints.order(), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn it_tries_endpoints_cooling_down_last() {
+        let endpoints = endpoints(Duration::from_secs(30));
+
+        endpoints.failed(0);
+        endpoints.failed(2);
+
+        assert_eq!(endpoints.order(), vec![1, 0, 2]);
+
+        endpoints.succeeded(2);
+
+        assert_eq!(endpoints.order(), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn it_recovers_endpoints_after_the_cooldown() {
+        let endpoints = endpoints(Duration::from_millis(10));
+
+        endpoints.failed(0);
+
+        assert_eq!(endpoints.order(), vec![1, 2, 0]);
+
+        thread::sleep(Duration::from_millis(20));
+
+        assert_eq!(endpoints.order(), vec![0, 1, 2]);
+    }
+}
@@ -1,67 +1,531 @@
 use std::error::Error;
 use std::fmt;
+use std::io;
 
-use hyper::client::Response;
 use hyper::error::HttpError;
-use rustc_serialize::json::Json;
+use rustc_serialize::json::{Json, ParserError};
+use url::ParseError;
+
+use schema::{SchedulingError, Unit, UnitNameError, UnitState, UnitStates};
+use snapshot;
+use transport::Response;
 
 /// An error returned by `Client` when an API call fails.
-pub struct FleetError {
-    /// An HTTP status code returned by the fleet API.
-    pub code: Option<u16>,
-    /// A message describing the error. This message comes from fleet directly whenever fleet
-    /// provides a message.
-    pub message: Option<String>,
+#[derive(Debug)]
+pub enum FleetError {
+    /// The request could not be sent or its response could not be read, e.g. because of a network
+    /// error. The underlying error is available through `Error::cause`.
+    Transport(Box<Error + Send + Sync>),
+    /// No response was received within one of the timeouts set with `ClientBuilder`.
+    Timeout(TimeoutKind),
+    /// The TLS settings given to `ClientBuilder` could not be loaded, or a secure connection to
+    /// fleet could not be established, e.g. because fleet's certificate could not be verified.
+    Tls(TlsError),
+    /// A URL could not be parsed.
+    Url(ParseError),
+    /// A response from fleet was not valid JSON.
+    Json(ParserError),
+    /// A response from fleet was valid JSON but did not have the expected structure.
+    Decode(DecodeError),
+    /// The fleet API returned an explicit error.
+    Api(ApiError),
+    /// A unit name was invalid. No request was sent to fleet.
+    UnitName(UnitNameError),
+    /// A unit's `[X-Fleet]` section was invalid. No request was sent to fleet.
+    Scheduling(SchedulingError),
+    /// The unit `Client::wait_for_unit` waited for did not reach the target state in time.
+    WaitTimeout(WaitTimeout),
+    /// A cluster snapshot could not be read or imported.
+    Snapshot(SnapshotError),
+    /// A request made by a `Client` with more than one endpoint failed. Contains the root URL of
+    /// the endpoint the request was last sent to and the error that occurred there.
+    Endpoint(String, Box<FleetError>),
+    /// A change in a reconcile `Plan` could not be applied. Contains a description of the change
+    /// and the error that occurred while applying it.
+    Apply(String, Box<FleetError>),
 }
 
 impl FleetError {
-    /// Constructs a new `FleetError` from a `hyper::error::HttpError`. Not intended for public
-    /// use.
-    pub fn from_hyper_error(error: &HttpError) -> FleetError {
-        FleetError {
-            code: None,
-            message: Some(error.description().to_string()),
+    /// Returns the HTTP status code returned by the fleet API, if the error came from fleet.
+    pub fn code(&self) -> Option<u16> {
+        match *self {
+            FleetError::Api(ref error) => Some(error.code),
+            FleetError::Endpoint(_, ref error) => error.code(),
+            FleetError::Apply(_, ref error) => error.code(),
+            _ => None,
         }
     }
 
-    /// Constructs a new `FleetError` from a `hyper::client::Response`. Not intended for public
-    /// use.
-    pub fn from_hyper_response(response: &mut Response) -> FleetError {
-        FleetError {
-            code: Some(response.status.to_u16()),
-            message: extract_message(response),
+    /// Returns the kind of API error, if the error came from fleet.
+    pub fn api_kind(&self) -> Option<ApiErrorKind> {
+        match *self {
+            FleetError::Api(ref error) => Some(error.kind),
+            FleetError::Endpoint(_, ref error) => error.api_kind(),
+            FleetError::Apply(_, ref error) => error.api_kind(),
+            _ => None,
+        }
+    }
+
+    /// Returns the root URL of the endpoint where the error occurred, if the `Client` has more
+    /// than one endpoint.
+    pub fn endpoint(&self) -> Option<&str> {
+        match *self {
+            FleetError::Endpoint(ref endpoint, _) => Some(&endpoint[..]),
+            FleetError::Apply(_, ref error) => error.endpoint(),
+            _ => None,
         }
     }
 }
 
 impl fmt::Display for FleetError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
+        match *self {
+            FleetError::Transport(ref error) => write!(f, "HTTP request failed: {}", error),
+            FleetError::Timeout(ref kind) => write!(f, "HTTP request failed: {}", kind),
+            FleetError::Tls(ref error) => write!(f, "HTTP request failed: {}", error),
+            FleetError::Url(ref error) => write!(f, "Invalid URL: {}", error),
+            FleetError::Json(ref error) => write!(f, "Invalid JSON response from fleet: {}", error),
+            FleetError::Decode(ref error) => write!(f, "Unexpected response from fleet: {}", error),
+            FleetError::Api(ref error) => write!(f, "{}", error),
+            FleetError::UnitName(ref error) => write!(f, "{}", error),
+            FleetError::Scheduling(ref error) => write!(f, "{}", error),
+            FleetError::WaitTimeout(ref error) => write!(f, "{}", error),
+            FleetError::Snapshot(ref error) => write!(f, "{}", error),
+            FleetError::Endpoint(ref endpoint, ref error) => {
+                write!(f, "{} (fleet endpoint {})", error, endpoint)
+            },
+            FleetError::Apply(ref change, ref error) => {
+                write!(f, "Failed to {}: {}", change, error)
+            },
+        }
+    }
+}
+
+impl Error for FleetError {
+    fn description(&self) -> &str {
+        match *self {
+            FleetError::Transport(ref error) => error.description(),
+            FleetError::Timeout(ref kind) => kind.description(),
+            FleetError::Tls(ref error) => error.description(),
+            FleetError::Url(ref error) => error.description(),
+            FleetError::Json(ref error) => error.description(),
+            FleetError::Decode(ref error) => error.description(),
+            FleetError::Api(ref error) => error.description(),
+            FleetError::UnitName(ref error) => error.description(),
+            FleetError::Scheduling(ref error) => error.description(),
+            FleetError::WaitTimeout(ref error) => error.description(),
+            FleetError::Snapshot(ref error) => error.description(),
+            FleetError::Endpoint(_, ref error) => error.description(),
+            FleetError::Apply(_, ref error) => error.description(),
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            FleetError::Transport(ref error) => Some(&**error),
+            FleetError::Url(ref error) => Some(error),
+            FleetError::Json(ref error) => Some(error),
+            FleetError::Endpoint(_, ref error) | FleetError::Apply(_, ref error) => Some(&**error),
+            FleetError::Timeout(_) | FleetError::Tls(_) => None,
+            FleetError::Decode(_) | FleetError::Api(_) | FleetError::WaitTimeout(_) => None,
+            FleetError::Snapshot(_) | FleetError::UnitName(_) | FleetError::Scheduling(_) => None,
+        }
+    }
+}
+
+impl From<HttpError> for FleetError {
+    fn from(error: HttpError) -> FleetError {
+        match error {
+            HttpError::HttpIoError(error) => FleetError::from(error),
+            error => FleetError::Transport(Box::new(error)),
+        }
+    }
+}
+
+impl From<io::Error> for FleetError {
+    fn from(error: io::Error) -> FleetError {
+        let timeout = match error.get_ref() {
+            Some(inner) => inner.downcast_ref::<TimeoutKind>().map(|kind| *kind),
+            None => None,
+        };
+
+        if let Some(kind) = timeout {
+            return FleetError::Timeout(kind);
+        }
+
+        if error.get_ref().map_or(false, |inner| inner.is::<TlsError>()) {
+            let inner = error.into_inner().unwrap().downcast::<TlsError>().ok().unwrap();
+
+            return FleetError::Tls(*inner);
+        }
+
+        FleetError::Transport(Box::new(error))
+    }
+}
+
+impl From<TlsError> for FleetError {
+    fn from(error: TlsError) -> FleetError {
+        FleetError::Tls(error)
+    }
+}
+
+impl From<UnitNameError> for FleetError {
+    fn from(error: UnitNameError) -> FleetError {
+        FleetError::UnitName(error)
+    }
+}
+
+impl From<SchedulingError> for FleetError {
+    fn from(error: SchedulingError) -> FleetError {
+        FleetError::Scheduling(error)
+    }
+}
+
+impl From<ParseError> for FleetError {
+    fn from(error: ParseError) -> FleetError {
+        FleetError::Url(error)
+    }
+}
+
+impl From<ParserError> for FleetError {
+    fn from(error: ParserError) -> FleetError {
+        FleetError::Json(error)
+    }
+}
+
+/// The timeouts that can expire while `Client` makes a request.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeoutKind {
+    /// A connection to fleet could not be established within the connect timeout.
+    Connect,
+    /// fleet did not send any data within the read timeout.
+    Read,
+    /// The request could not be sent to fleet within the write timeout.
+    Write,
+    /// The request as a whole, including connecting and reading the full response, did not
+    /// finish within the overall request timeout.
+    Request,
+}
+
+impl fmt::Display for TimeoutKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.description())
+    }
+}
+
+impl Error for TimeoutKind {
+    fn description(&self) -> &str {
+        match *self {
+            TimeoutKind::Connect => "timed out connecting to fleet",
+            TimeoutKind::Read => "timed out waiting for fleet to respond",
+            TimeoutKind::Write => "timed out sending the request to fleet",
+            TimeoutKind::Request => "request to fleet did not finish within its timeout",
+        }
+    }
+}
+
+/// An error setting up a secure connection to fleet.
+#[derive(Debug)]
+pub struct TlsError {
+    /// A message describing the error, including the file or host involved.
+    pub message: String,
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for TlsError {
+    fn description(&self) -> &str {
+        &self.message[..]
+    }
+}
+
+/// A description of how far a unit got before `Client::wait_for_unit` gave up on it.
+#[derive(Debug)]
+pub struct WaitTimeout {
+    /// The name of the unit.
+    pub name: String,
+    /// The state that was waited for.
+    pub target: UnitStates,
+    /// The unit as last reported by fleet, or `None` if it did not exist.
+    pub unit: Option<Unit>,
+    /// The states last reported for the unit by each machine it is scheduled to.
+    pub states: Vec<UnitState>,
+}
+
+impl fmt::Display for WaitTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "Timed out waiting for unit {} to be {}: ", self.name, self.target));
+
+        let unit = match self.unit {
+            Some(ref unit) => unit,
+            None => return write!(f, "unit does not exist"),
+        };
+
+        try!(write!(
             f,
-            "{}: {}",
-            self.code.unwrap_or(0),
-            self.message.clone().unwrap_or("Unknown error".to_string()),
-       )
+            "current state {}, desired state {}",
+            unit.current_state,
+            unit.desired_state
+        ));
+
+        for state in self.states.iter() {
+            let machine_id = state.machine_id.as_ref().map(|id| &id[..]).unwrap_or("no machine");
+
+            try!(write!(
+                f,
+                "; {} ({}) on {}",
+                state.systemd_active_state,
+                state.systemd_sub_state,
+                machine_id
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for WaitTimeout {
+    fn description(&self) -> &str {
+        "timed out waiting for unit"
+    }
+}
+
+/// An error reading or importing a cluster snapshot.
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The snapshot is not valid JSON or does not have the expected structure. Contains a
+    /// description of the problem.
+    Invalid(String),
+    /// The snapshot was written in a newer format than this version of the crate can read.
+    /// Contains the snapshot's format version.
+    UnsupportedVersion(u64),
+    /// Units in the snapshot already exist in the cluster and `Conflict::Fail` was requested.
+    /// Contains the names of the units.
+    Conflict(Vec<String>),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SnapshotError::Invalid(ref message) => write!(f, "Invalid snapshot: {}", message),
+            SnapshotError::UnsupportedVersion(version) => write!(
+                f,
+                "Snapshot format version {} is newer than the supported version {}",
+                version,
+                snapshot::VERSION
+            ),
+            SnapshotError::Conflict(ref names) => {
+                write!(f, "Units already exist in the cluster: {}", names.join(", "))
+            },
+        }
+    }
+}
+
+impl Error for SnapshotError {
+    fn description(&self) -> &str {
+        match *self {
+            SnapshotError::Invalid(_) => "invalid snapshot",
+            SnapshotError::UnsupportedVersion(_) => "unsupported snapshot version",
+            SnapshotError::Conflict(_) => "units in snapshot already exist",
+        }
+    }
+}
+
+/// The broad categories of errors the fleet API returns, derived from the HTTP status code.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ApiErrorKind {
+    /// The request was malformed (400).
+    BadRequest,
+    /// The requested resource does not exist (404).
+    NotFound,
+    /// The request conflicts with the current state of the resource (409), e.g. creating a unit
+    /// without options.
+    Conflict,
+    /// fleet failed to handle the request (5xx).
+    ServerError,
+    /// Any other unsuccessful status code.
+    Other,
+}
+
+impl ApiErrorKind {
+    /// Returns the `ApiErrorKind` corresponding to an HTTP status code.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use fleet::ApiErrorKind;
+    /// assert_eq!(ApiErrorKind::from_status(409), ApiErrorKind::Conflict);
+    /// ```
+    pub fn from_status(status: u16) -> ApiErrorKind {
+        match status {
+            400 => ApiErrorKind::BadRequest,
+            404 => ApiErrorKind::NotFound,
+            409 => ApiErrorKind::Conflict,
+            500...599 => ApiErrorKind::ServerError,
+            _ => ApiErrorKind::Other,
+        }
+    }
+}
+
+/// An error returned explicitly by the fleet API.
+#[derive(Debug)]
+pub struct ApiError {
+    /// The category of error, derived from `code`.
+    pub kind: ApiErrorKind,
+    /// The HTTP status code returned by the fleet API.
+    pub code: u16,
+    /// A message describing the error. This message comes from fleet directly whenever fleet
+    /// provides a message.
+    pub message: String,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl Error for ApiError {
+    fn description(&self) -> &str {
+        &self.message[..]
+    }
+}
+
+/// An error describing which part of a response from fleet could not be decoded.
+#[derive(Debug)]
+pub struct DecodeError {
+    /// The kind of resource that was being decoded, such as "unit" or "machine".
+    pub resource: &'static str,
+    /// The name of the JSON field that could not be decoded. Empty if the resource itself was
+    /// invalid.
+    pub field: String,
+    /// What was wrong with the field.
+    pub message: String,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.field.is_empty() {
+            write!(f, "{} {}", self.resource, self.message)
+        } else {
+            write!(f, "{} field `{}` {}", self.resource, self.field, self.message)
+        }
+    }
+}
+
+impl Error for DecodeError {
+    fn description(&self) -> &str {
+        "invalid response from fleet"
     }
 }
 
-fn extract_message(response: &mut Response) -> Option<String> {
-   match Json::from_reader(response) {
+/// Constructs a new `FleetError` from an unsuccessful `Response`.
+pub fn from_response(response: &Response) -> FleetError {
+    FleetError::Api(ApiError {
+        kind: ApiErrorKind::from_status(response.status),
+        code: response.status,
+        message: extract_message(&response.body),
+    })
+}
+
+fn extract_message(body: &str) -> String {
+   match Json::from_str(body) {
        Ok(json) => {
            match json.find_path(&["error", "message"]) {
                Some(message_json) => match message_json.as_string() {
                    Some(message) => {
                        if message.len() == 0 {
-                           Some("Error in JSON response from Fleet was empty".to_string())
+                           "Error in JSON response from Fleet was empty".to_string()
                        } else {
-                           Some(message.to_string())
+                           message.to_string()
                        }
                    },
-                   None => Some("Error in JSON response from Fleet was empty".to_string()),
+                   None => "Error in JSON response from Fleet was empty".to_string(),
                },
-               None => Some("Error parsing JSON response from Fleet".to_string()),
+               None => "Error parsing JSON response from Fleet".to_string(),
            }
        },
-       Err(error) => Some(error.description().to_string()),
+       Err(error) => error.description().to_string(),
    }
 }
+
+#[cfg(test)]
+mod fleet_error_tests {
+    use std::error::Error;
+    use std::io;
+
+    use super::{from_response, ApiErrorKind, FleetError, TimeoutKind, TlsError};
+    use transport::Response;
+
+    #[test]
+    fn it_classifies_api_errors() {
+        let response = Response {
+            status: 409,
+            body: r#"{"error":{"code":409,"message":"unit does not exist and options field empty"}}"#
+                .to_string(),
+        };
+
+        let error = from_response(&response);
+
+        assert_eq!(error.code(), Some(409));
+        assert_eq!(error.api_kind(), Some(ApiErrorKind::Conflict));
+        assert_eq!(format!("{}", error), "409: unit does not exist and options field empty");
+
+        match error {
+            FleetError::Api(api_error) => {
+                assert_eq!(api_error.message, "unit does not exist and options field empty");
+            },
+            _ => panic!("expected an API error"),
+        }
+    }
+
+    #[test]
+    fn it_classifies_server_errors() {
+        assert_eq!(ApiErrorKind::from_status(503), ApiErrorKind::ServerError);
+    }
+
+    #[test]
+    fn it_chains_the_underlying_error() {
+        let error = FleetError::from(io::Error::new(io::ErrorKind::Other, "connection reset"));
+
+        assert!(error.code().is_none());
+        assert_eq!(format!("{}", error.cause().unwrap()), "connection reset");
+    }
+
+    #[test]
+    fn it_recognizes_timeouts() {
+        let io_error = io::Error::new(io::ErrorKind::TimedOut, TimeoutKind::Connect);
+        let error = FleetError::from(io_error);
+
+        match error {
+            FleetError::Timeout(TimeoutKind::Connect) => {},
+            _ => panic!("expected a connect timeout"),
+        }
+
+        assert_eq!(format!("{}", error), "HTTP request failed: timed out connecting to fleet");
+    }
+
+    #[test]
+    fn it_recognizes_tls_errors() {
+        let tls_error = TlsError {
+            message: "TLS handshake with `localhost` failed".to_string(),
+        };
+        let error = FleetError::from(io::Error::new(io::ErrorKind::Other, tls_error));
+
+        match error {
+            FleetError::Tls(ref error) => {
+                assert_eq!(error.message, "TLS handshake with `localhost` failed");
+            },
+            _ => panic!("expected a TLS error"),
+        }
+
+        assert_eq!(
+            format!("{}", error),
+            "HTTP request failed: TLS handshake with `localhost` failed"
+        );
+    }
+}
@@ -0,0 +1,754 @@
+//! An in-memory fake of the fleet API for tests, enabled with the `fake` cargo feature.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use rustc_serialize::json::Json;
+use url::form_urlencoded;
+use url::percent_encoding::percent_decode;
+
+use client::Client;
+use schema::{unit_hash, UnitOption, UnitStates};
+use serialize;
+
+/// A local HTTP server implementing the fleet v1 API with in-memory state, for testing code that
+/// uses `Client` without a real fleet cluster.
+///
+/// The server listens on a random port on `127.0.0.1` and serves the `/units`, `/state` and
+/// `/machines` endpoints, returning the same status codes and error bodies as fleet. Collections
+/// are paginated with opaque `nextPageToken`s, 100 items per page by default.
+///
+/// Units are scheduled as soon as their desired state is `loaded` or `launched` and a machine is
+/// available, preferring the machine named by `MachineID` in the `[X-Fleet]` section and otherwise
+/// the machine with the fewest units. Scheduled units immediately reach their desired state.
+///
+/// The server shuts down when the `FakeFleet` is dropped, closing any connections clients still
+/// have open to it.
+///
+/// # Examples
+///
+/// ```
+/// use fleet::FakeFleet;
+///
+/// let fake = FakeFleet::start().unwrap();
+/// fake.add_machine("abc123", "10.0.0.1", &[("region", "us-east-1")]);
+///
+/// let client = fake.client();
+/// let machine_page = client.list_machines(None).ok().unwrap();
+///
+/// assert_eq!(machine_page.machines[0].id, "abc123");
+/// ```
+pub struct FakeFleet {
+    address: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    state: Arc<Mutex<State>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl FakeFleet {
+    /// Starts a new server without any units or machines.
+    ///
+    /// # Failures
+    ///
+    /// Returns an `io::Error` if the server cannot listen on a local port.
+    pub fn start() -> io::Result<FakeFleet> {
+        let listener = try!(TcpListener::bind("127.0.0.1:0"));
+        let address = try!(listener.local_addr());
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let state = Arc::new(Mutex::new(State::new()));
+
+        let thread_shutdown = shutdown.clone();
+        let thread_state = state.clone();
+
+        let thread = thread::spawn(move || {
+            let mut connections = vec![];
+
+            for stream in listener.incoming() {
+                if thread_shutdown.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                if let Ok(stream) = stream {
+                    let state = thread_state.clone();
+                    let shutdown = thread_shutdown.clone();
+
+                    connections.push(thread::spawn(move || {
+                        handle_connection(stream, state, shutdown)
+                    }));
+                }
+            }
+
+            for connection in connections {
+                let _ = connection.join();
+            }
+        });
+
+        Ok(FakeFleet {
+            address: address,
+            shutdown: shutdown,
+            state: state,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns the root URL of the server, suitable for `Client::new`.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.address)
+    }
+
+    /// Returns a `Client` connected to the server.
+    pub fn client(&self) -> Client {
+        Client::new(&self.url()).ok().expect("the fake fleet URL is always valid")
+    }
+
+    /// Adds a machine to the cluster and schedules any units that were waiting for one.
+    pub fn add_machine(&self, id: &str, primary_ip: &str, metadata: &[(&str, &str)]) {
+        let mut state = self.state.lock().unwrap();
+
+        state.machines.push(FakeMachine {
+            id: id.to_string(),
+            primary_ip: primary_ip.to_string(),
+            metadata: metadata.iter().map(|&(k, v)| (k.to_string(), v.to_string())).collect(),
+        });
+
+        state.schedule();
+    }
+
+    /// Removes a machine from the cluster. Units scheduled to it are rescheduled to the remaining
+    /// machines, if any.
+    pub fn remove_machine(&self, id: &str) {
+        let mut state = self.state.lock().unwrap();
+
+        state.machines.retain(|machine| machine.id != id);
+
+        for unit in state.units.values_mut() {
+            if unit.machine_id.as_ref().map_or(false, |machine_id| machine_id == id) {
+                unit.machine_id = None;
+                unit.current_state = "inactive".to_string();
+            }
+        }
+
+        state.schedule();
+    }
+
+    /// Sets the number of items returned in each page of a collection.
+    pub fn set_page_size(&self, page_size: usize) {
+        self.state.lock().unwrap().page_size = page_size;
+    }
+
+    /// Makes the server wait for `delay` before sending each response, as a slow or distant fleet
+    /// would. Requests on different connections are delayed independently.
+    pub fn set_response_delay(&self, delay: Duration) {
+        self.state.lock().unwrap().response_delay = delay;
+    }
+}
+
+impl Drop for FakeFleet {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+
+        // Wake up the listener so it notices the shutdown flag.
+        let _ = TcpStream::connect(self.address);
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+struct FakeMachine {
+    id: String,
+    primary_ip: String,
+    metadata: HashMap<String, String>,
+}
+
+struct FakeUnit {
+    name: String,
+    current_state: String,
+    desired_state: String,
+    machine_id: Option<String>,
+    options: Vec<UnitOption>,
+}
+
+struct State {
+    machines: Vec<FakeMachine>,
+    page_size: usize,
+    response_delay: Duration,
+    units: BTreeMap<String, FakeUnit>,
+}
+
+impl State {
+    fn new() -> State {
+        State {
+            machines: vec![],
+            page_size: 100,
+            response_delay: Duration::from_secs(0),
+            units: BTreeMap::new(),
+        }
+    }
+
+    fn schedule(&mut self) {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+
+        for unit in self.units.values() {
+            if let Some(ref machine_id) = unit.machine_id {
+                *counts.entry(machine_id.clone()).or_insert(0) += 1;
+            }
+        }
+
+        for unit in self.units.values_mut() {
+            if unit.desired_state == "inactive" {
+                unit.machine_id = None;
+                unit.current_state = "inactive".to_string();
+                continue;
+            }
+
+            if unit.machine_id.is_none() {
+                let required = unit.options.iter()
+                    .find(|option| option.section == "X-Fleet" && option.name == "MachineID")
+                    .map(|option| option.value.clone());
+
+                let machine = match required {
+                    Some(id) => self.machines.iter().find(|machine| machine.id == id),
+                    None => self.machines.iter().min_by_key(|machine| {
+                        counts.get(&machine.id).cloned().unwrap_or(0)
+                    }),
+                };
+
+                if let Some(machine) = machine {
+                    *counts.entry(machine.id.clone()).or_insert(0) += 1;
+                    unit.machine_id = Some(machine.id.clone());
+                }
+            }
+
+            if unit.machine_id.is_some() {
+                unit.current_state = unit.desired_state.clone();
+            }
+        }
+    }
+}
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    body: Vec<u8>,
+    close: bool,
+}
+
+// How often a connection waiting for its next request checks whether the server is shutting down.
+const SHUTDOWN_POLL_INTERVAL: u64 = 50;
+
+fn handle_connection(stream: TcpStream, state: Arc<Mutex<State>>, shutdown: Arc<AtomicBool>) {
+    let mut reader = match stream.try_clone() {
+        Ok(stream) => BufReader::new(stream),
+        Err(_) => return,
+    };
+    let mut writer = stream;
+
+    loop {
+        if !wait_for_request(&mut reader, &writer, &shutdown) {
+            return;
+        }
+
+        let request = match read_request(&mut reader) {
+            Ok(Some(request)) => request,
+            _ => return,
+        };
+
+        let ((status, body), delay) = {
+            let mut state = state.lock().unwrap();
+
+            (route(&mut state, &request), state.response_delay)
+        };
+
+        thread::sleep(delay);
+
+        if write_response(&mut writer, status, &body).is_err() || request.close {
+            return;
+        }
+    }
+}
+
+// Waits until the next request starts to arrive, returning false if the client closed the
+// connection or the server is shutting down first.
+fn wait_for_request(
+    reader: &mut BufReader<TcpStream>,
+    stream: &TcpStream,
+    shutdown: &AtomicBool,
+) -> bool {
+    let poll_interval = Some(Duration::from_millis(SHUTDOWN_POLL_INTERVAL));
+
+    if stream.set_read_timeout(poll_interval).is_err() {
+        return false;
+    }
+
+    loop {
+        if shutdown.load(Ordering::SeqCst) {
+            return false;
+        }
+
+        match reader.fill_buf() {
+            Ok(buffer) => {
+                // The rest of the request is read without a timeout.
+                return !buffer.is_empty() && stream.set_read_timeout(None).is_ok();
+            },
+            Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => {},
+            Err(ref error) if error.kind() == io::ErrorKind::TimedOut => {},
+            Err(_) => return false,
+        }
+    }
+}
+
+fn read_request<R: BufRead>(reader: &mut R) -> io::Result<Option<HttpRequest>> {
+    let mut line = String::new();
+
+    if try!(reader.read_line(&mut line)) == 0 {
+        return Ok(None);
+    }
+
+    let parts: Vec<&str> = line.trim().split(' ').collect();
+
+    if parts.len() != 3 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "malformed request line"));
+    }
+
+    let mut content_length = 0;
+    let mut close = parts[2] == "HTTP/1.0";
+
+    loop {
+        let mut header = String::new();
+
+        if try!(reader.read_line(&mut header)) == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim();
+
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some(colon) = header.find(':') {
+            let name = header[..colon].trim().to_lowercase();
+            let value = header[colon + 1..].trim();
+
+            if name == "content-length" {
+                content_length = match value.parse() {
+                    Ok(length) => length,
+                    Err(_) => return Err(
+                        io::Error::new(io::ErrorKind::InvalidData, "invalid Content-Length")
+                    ),
+                };
+            } else if name == "connection" {
+                close = value.to_lowercase() == "close";
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length];
+
+    try!(reader.read_exact(&mut body));
+
+    let (path, query) = match parts[1].find('?') {
+        Some(index) => {
+            (&parts[1][..index], form_urlencoded::parse(parts[1][index + 1..].as_bytes()))
+        },
+        None => (parts[1], vec![]),
+    };
+
+    Ok(Some(HttpRequest {
+        method: parts[0].to_string(),
+        path: String::from_utf8_lossy(&percent_decode(path.as_bytes())).into_owned(),
+        query: query,
+        body: body,
+        close: close,
+    }))
+}
+
+fn write_response<W: Write>(writer: &mut W, status: u16, body: &str) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    };
+
+    try!(write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    ));
+
+    writer.flush()
+}
+
+fn route(state: &mut State, request: &HttpRequest) -> (u16, String) {
+    let path = if request.path.starts_with("/fleet/v1/") {
+        &request.path["/fleet/v1".len()..]
+    } else {
+        return error(404, "not found");
+    };
+
+    let method = &request.method[..];
+
+    match path {
+        "/units" => match method {
+            "GET" => list_units(state, request),
+            _ => error(400, "only HTTP GET supported against this resource"),
+        },
+        "/state" => match method {
+            "GET" => list_unit_states(state, request),
+            _ => error(400, "only HTTP GET supported against this resource"),
+        },
+        "/machines" => match method {
+            "GET" => list_machines(state, request),
+            _ => error(400, "only HTTP GET supported against this resource"),
+        },
+        _ if path.starts_with("/units/") && path.len() > "/units/".len() => {
+            let name = &path["/units/".len()..];
+
+            match method {
+                "GET" => get_unit(state, name),
+                "PUT" => set_unit(state, name, &request.body),
+                "DELETE" => destroy_unit(state, name),
+                _ => error(405, "only GET, PUT and DELETE supported against this resource"),
+            }
+        },
+        _ => error(404, "not found"),
+    }
+}
+
+fn get_unit(state: &State, name: &str) -> (u16, String) {
+    match state.units.get(name) {
+        Some(unit) => (200, unit_to_json(unit).to_string()),
+        None => error(404, "unit does not exist"),
+    }
+}
+
+fn set_unit(state: &mut State, name: &str, body: &[u8]) -> (u16, String) {
+    let json = match Json::from_str(&String::from_utf8_lossy(body)) {
+        Ok(json) => json,
+        Err(err) => return error(400, &format!("unable to decode body: {}", err)),
+    };
+
+    if let Some(body_name) = json.find("name").and_then(|name| name.as_string()) {
+        if body_name != name {
+            return error(400, &format!(
+                "name in URL {:?} differs from unit name in request body {:?}",
+                name,
+                body_name
+            ));
+        }
+    }
+
+    let desired_state = match json.find("desiredState").and_then(|state| state.as_string()) {
+        Some(desired_state) => match UnitStates::from_str(desired_state) {
+            Some(_) => Some(desired_state.to_string()),
+            None => return error(400, &format!("invalid desiredState: {}", desired_state)),
+        },
+        None => None,
+    };
+
+    let mut options = vec![];
+
+    if let Some(options_json) = json.find("options").and_then(|options| options.as_array()) {
+        for option_json in options_json.iter() {
+            match serialize::unit_option_from_json(option_json) {
+                Ok(option) => options.push(option),
+                Err(err) => return error(400, &format!("unable to decode body: {}", err)),
+            }
+        }
+    }
+
+    let is_template = name.contains("@.");
+
+    if is_template && desired_state.as_ref().map_or(false, |desired| desired != "inactive") {
+        return error(400, &format!("cannot activate template {:?}", name));
+    }
+
+    let status = if state.units.contains_key(name) {
+        match desired_state {
+            Some(desired_state) => {
+                state.units.get_mut(name).unwrap().desired_state = desired_state;
+
+                204
+            },
+            None => return error(409, "must provide DesiredState to update existing unit"),
+        }
+    } else {
+        if options.is_empty() {
+            return error(409, "unit does not exist and options field empty");
+        }
+
+        state.units.insert(name.to_string(), FakeUnit {
+            name: name.to_string(),
+            current_state: "inactive".to_string(),
+            desired_state: desired_state.unwrap_or("inactive".to_string()),
+            machine_id: None,
+            options: options,
+        });
+
+        201
+    };
+
+    state.schedule();
+
+    (status, String::new())
+}
+
+fn destroy_unit(state: &mut State, name: &str) -> (u16, String) {
+    match state.units.remove(name) {
+        Some(_) => (204, String::new()),
+        None => error(404, "unit does not exist"),
+    }
+}
+
+fn list_units(state: &State, request: &HttpRequest) -> (u16, String) {
+    let units: Vec<Json> = state.units.values().map(unit_to_json).collect();
+
+    page(state, request, "units", units)
+}
+
+fn list_unit_states(state: &State, request: &HttpRequest) -> (u16, String) {
+    let machine_id = query_value(request, "machineID");
+    let unit_name = query_value(request, "unitName");
+
+    let states: Vec<Json> = state.units.values().filter(|unit| {
+        unit.machine_id.is_some() &&
+            machine_id.map_or(true, |id| unit.machine_id.as_ref().unwrap() == id) &&
+            unit_name.map_or(true, |name| unit.name == name)
+    }).map(unit_state_to_json).collect();
+
+    page(state, request, "states", states)
+}
+
+fn list_machines(state: &State, request: &HttpRequest) -> (u16, String) {
+    let machines: Vec<Json> = state.machines.iter().map(machine_to_json).collect();
+
+    page(state, request, "machines", machines)
+}
+
+fn query_value<'a>(request: &'a HttpRequest, key: &str) -> Option<&'a str> {
+    request.query.iter().find(|&&(ref k, _)| k == key).map(|&(_, ref v)| &v[..])
+}
+
+fn page(state: &State, request: &HttpRequest, key: &str, items: Vec<Json>) -> (u16, String) {
+    let start = match query_value(request, "nextPageToken") {
+        Some(token) => match parse_page_token(token) {
+            Some(start) if start <= items.len() => start,
+            _ => return error(400, "invalid value for nextPageToken"),
+        },
+        None => 0,
+    };
+
+    let end = ::std::cmp::min(start + state.page_size, items.len());
+    let mut object = BTreeMap::new();
+
+    // fleet omits empty collections and the token for the last page.
+    if end > start {
+        object.insert(key.to_string(), Json::Array(items[start..end].to_vec()));
+    }
+
+    if end < items.len() {
+        object.insert("nextPageToken".to_string(), Json::String(format!("page-{}", end)));
+    }
+
+    (200, Json::Object(object).to_string())
+}
+
+fn parse_page_token(token: &str) -> Option<usize> {
+    if token.starts_with("page-") {
+        token["page-".len()..].parse().ok()
+    } else {
+        None
+    }
+}
+
+fn error(code: u16, message: &str) -> (u16, String) {
+    let mut error = BTreeMap::new();
+    error.insert("code".to_string(), Json::U64(code as u64));
+    error.insert("message".to_string(), Json::String(message.to_string()));
+
+    let mut object = BTreeMap::new();
+    object.insert("error".to_string(), Json::Object(error));
+
+    (code, Json::Object(object).to_string())
+}
+
+fn string(value: &str) -> Json {
+    Json::String(value.to_string())
+}
+
+fn unit_to_json(unit: &FakeUnit) -> Json {
+    let options = unit.options.iter().map(|option| {
+        let mut object = BTreeMap::new();
+        object.insert("name".to_string(), string(&option.name));
+        object.insert("section".to_string(), string(&option.section));
+        object.insert("value".to_string(), string(&option.value));
+
+        Json::Object(object)
+    }).collect();
+
+    let mut object = BTreeMap::new();
+    object.insert("currentState".to_string(), string(&unit.current_state));
+    object.insert("desiredState".to_string(), string(&unit.desired_state));
+    object.insert("name".to_string(), string(&unit.name));
+    object.insert("options".to_string(), Json::Array(options));
+
+    if let Some(ref machine_id) = unit.machine_id {
+        object.insert("machineID".to_string(), string(machine_id));
+    }
+
+    Json::Object(object)
+}
+
+fn unit_state_to_json(unit: &FakeUnit) -> Json {
+    let (active_state, sub_state) = if unit.current_state == "launched" {
+        ("active", "running")
+    } else {
+        ("inactive", "dead")
+    };
+
+    let mut object = BTreeMap::new();
+    object.insert("hash".to_string(), string(&unit_hash(&unit.options)));
+    object.insert("machineID".to_string(), string(unit.machine_id.as_ref().unwrap()));
+    object.insert("name".to_string(), string(&unit.name));
+    object.insert("systemdActiveState".to_string(), string(active_state));
+    object.insert("systemdLoadState".to_string(), string("loaded"));
+    object.insert("systemdSubState".to_string(), string(sub_state));
+
+    Json::Object(object)
+}
+
+fn machine_to_json(machine: &FakeMachine) -> Json {
+    let mut metadata = BTreeMap::new();
+
+    for (key, value) in machine.metadata.iter() {
+        metadata.insert(key.clone(), string(value));
+    }
+
+    let mut object = BTreeMap::new();
+    object.insert("id".to_string(), string(&machine.id));
+    object.insert("primaryIP".to_string(), string(&machine.primary_ip));
+
+    if !metadata.is_empty() {
+        object.insert("metadata".to_string(), Json::Object(metadata));
+    }
+
+    Json::Object(object)
+}
+
+#[cfg(test)]
+mod fake_fleet_tests {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    use super::FakeFleet;
+    use error::ApiErrorKind;
+    use schema::{UnitOption, UnitStates};
+
+    fn options() -> Vec<UnitOption> {
+        vec![
+            UnitOption {
+                name: "ExecStart".to_string(),
+                section: "Service".to_string(),
+                value: "/usr/bin/sleep 3000".to_string(),
+            },
+        ]
+    }
+
+    #[test]
+    fn it_schedules_created_units() {
+        let fake = FakeFleet::start().unwrap();
+        fake.add_machine("abc123", "10.0.0.1", &[]);
+
+        let client = fake.client();
+        client.create_unit("test.service", UnitStates::Launched, options()).ok().unwrap();
+
+        let unit = client.get_unit("test.service").ok().unwrap();
+
+        assert_eq!(unit.machine_id, Some("abc123".to_string()));
+
+        let states = client.list_unit_states(None, None, None).ok().unwrap().states;
+
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[0].hash, unit.hash());
+        assert_eq!(states[0].systemd_sub_state, "running");
+    }
+
+    #[test]
+    fn it_returns_fleets_errors() {
+        let fake = FakeFleet::start().unwrap();
+        let client = fake.client();
+
+        let error = client.create_unit("test.service", UnitStates::Launched, vec![]).err().unwrap();
+
+        assert_eq!(error.api_kind(), Some(ApiErrorKind::Conflict));
+        assert_eq!(format!("{}", error), "409: unit does not exist and options field empty");
+
+        let error = client.get_unit("missing.service").err().unwrap();
+
+        assert_eq!(format!("{}", error), "404: unit does not exist");
+    }
+
+    #[test]
+    fn it_paginates_collections() {
+        let fake = FakeFleet::start().unwrap();
+        fake.set_page_size(2);
+
+        let client = fake.client();
+
+        for name in ["a.service", "b.service", "c.service"].iter() {
+            client.create_unit(name, UnitStates::Inactive, options()).ok().unwrap();
+        }
+
+        let first_page = client.list_units(None).ok().unwrap();
+
+        assert_eq!(first_page.units.len(), 2);
+
+        let second_page = client.list_units(first_page.next_page_token).ok().unwrap();
+
+        assert_eq!(second_page.units.len(), 1);
+        assert_eq!(second_page.units[0].name, "c.service");
+        assert!(second_page.next_page_token.is_none());
+    }
+
+    #[test]
+    fn it_rejects_invalid_page_tokens() {
+        let fake = FakeFleet::start().unwrap();
+        let client = fake.client();
+
+        let error = client.list_machines(Some("bogus".to_string())).err().unwrap();
+
+        assert_eq!(format!("{}", error), "400: invalid value for nextPageToken");
+    }
+
+    #[test]
+    fn it_closes_open_connections_when_dropped() {
+        let fake = FakeFleet::start().unwrap();
+        let mut stream = TcpStream::connect(fake.address).unwrap();
+
+        stream.write_all(b"GET /fleet/v1/machines HTTP/1.1\r\nHost: fleet\r\n\r\n").unwrap();
+
+        let mut buffer = [0; 1024];
+        assert!(stream.read(&mut buffer).unwrap() > 0);
+
+        drop(fake);
+
+        let mut rest = vec![];
+        stream.read_to_end(&mut rest).unwrap();
+    }
+}
@@ -0,0 +1,191 @@
+//! Glob patterns as fleet uses them for unit names, e.g. in `Conflicts=apache@*.service`.
+//!
+//! fleet matches these with Go's `path.Match`, so the same syntax is supported here: `*` matches
+//! any run of characters other than `/`, `?` matches a single character other than `/`,
+//! `[...]` matches a character class (negated with `[^...]`, with ranges such as `a-z`), and `\`
+//! escapes the following character.
+
+/// Returns an error describing the problem if `pattern` is not a valid glob pattern.
+pub fn validate(pattern: &str) -> Result<(), String> {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let mut index = 0;
+
+    while index < pattern.len() {
+        match pattern[index] {
+            '\\' => {
+                if index + 1 == pattern.len() {
+                    return Err("pattern ends with an unescaped `\\`".to_string());
+                }
+
+                index += 2;
+            },
+            '[' => match match_class(&pattern[index + 1..], '\0') {
+                Some((_, rest)) => index = pattern.len() - rest.len(),
+                None => return Err("pattern has a malformed character class".to_string()),
+            },
+            _ => index += 1,
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns whether `name` matches `pattern`. Malformed patterns never match.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    match_from(&pattern, &name)
+}
+
+fn match_from(pattern: &[char], name: &[char]) -> bool {
+    if pattern.is_empty() {
+        return name.is_empty();
+    }
+
+    match pattern[0] {
+        '*' => {
+            let mut index = 0;
+
+            loop {
+                if match_from(&pattern[1..], &name[index..]) {
+                    return true;
+                }
+
+                if index == name.len() || name[index] == '/' {
+                    return false;
+                }
+
+                index += 1;
+            }
+        },
+        '?' => !name.is_empty() && name[0] != '/' && match_from(&pattern[1..], &name[1..]),
+        '[' => {
+            if name.is_empty() || name[0] == '/' {
+                return false;
+            }
+
+            match match_class(&pattern[1..], name[0]) {
+                Some((matched, rest)) => matched && match_from(rest, &name[1..]),
+                None => false,
+            }
+        },
+        '\\' => {
+            pattern.len() > 1 &&
+                !name.is_empty() &&
+                pattern[1] == name[0] &&
+                match_from(&pattern[2..], &name[1..])
+        },
+        c => !name.is_empty() && c == name[0] && match_from(&pattern[1..], &name[1..]),
+    }
+}
+
+// Matches `c` against the character class at the start of `pattern`, which begins just after the
+// opening `[`. Returns whether it matched and the rest of the pattern after the closing `]`, or
+// `None` if the class is malformed.
+fn match_class(pattern: &[char], c: char) -> Option<(bool, &[char])> {
+    let mut index = 0;
+    let mut matched = false;
+    let mut ranges = 0;
+    let negated = pattern.first() == Some(&'^');
+
+    if negated {
+        index += 1;
+    }
+
+    loop {
+        if ranges > 0 && index < pattern.len() && pattern[index] == ']' {
+            return Some((matched != negated, &pattern[index + 1..]));
+        }
+
+        let (low, next) = match class_char(pattern, index) {
+            Some(result) => result,
+            None => return None,
+        };
+
+        let mut high = low;
+        index = next;
+
+        if index < pattern.len() && pattern[index] == '-' {
+            match class_char(pattern, index + 1) {
+                Some((character, next)) => {
+                    high = character;
+                    index = next;
+                },
+                None => return None,
+            }
+        }
+
+        if low <= c && c <= high {
+            matched = true;
+        }
+
+        ranges += 1;
+    }
+}
+
+fn class_char(pattern: &[char], index: usize) -> Option<(char, usize)> {
+    if index >= pattern.len() || pattern[index] == '-' || pattern[index] == ']' {
+        return None;
+    }
+
+    if pattern[index] == '\\' {
+        if index + 1 >= pattern.len() {
+            return None;
+        }
+
+        return Some((pattern[index + 1], index + 2));
+    }
+
+    Some((pattern[index], index + 1))
+}
+
+#[cfg(test)]
+mod glob_tests {
+    use super::{matches, validate};
+
+    #[test]
+    fn it_matches_literals() {
+        assert!(matches("apache.service", "apache.service"));
+        assert!(!matches("apache.service", "apache.socket"));
+    }
+
+    #[test]
+    fn it_matches_wildcards() {
+        assert!(matches("apache@*.service", "apache@1.service"));
+        assert!(matches("apache@*.service", "apache@.service"));
+        assert!(matches("*", "anything.service"));
+        assert!(!matches("apache@*.service", "nginx@1.service"));
+        assert!(!matches("*", "a/b"));
+    }
+
+    #[test]
+    fn it_matches_single_characters() {
+        assert!(matches("web?.service", "web1.service"));
+        assert!(!matches("web?.service", "web10.service"));
+    }
+
+    #[test]
+    fn it_matches_character_classes() {
+        assert!(matches("web[0-9].service", "web5.service"));
+        assert!(!matches("web[0-9].service", "webx.service"));
+        assert!(matches("web[^0-9].service", "webx.service"));
+        assert!(matches("web[abc].service", "webb.service"));
+    }
+
+    #[test]
+    fn it_matches_escaped_characters() {
+        assert!(matches("web\\*.service", "web*.service"));
+        assert!(!matches("web\\*.service", "web1.service"));
+    }
+
+    #[test]
+    fn it_validates_patterns() {
+        assert!(validate("apache@*.service").is_ok());
+        assert!(validate("web[0-9].service").is_ok());
+        assert!(validate("web[0-9.service").is_err());
+        assert!(validate("web[].service").is_err());
+        assert!(validate("web[a-].service").is_err());
+        assert!(validate("web\\").is_err());
+    }
+}
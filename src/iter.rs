@@ -0,0 +1,72 @@
+use std::vec;
+
+use error::FleetError;
+use schema::{Machine, Unit, UnitState};
+
+// The function a `PageIter` calls to request a page. It is given the token of the page to request,
+// or `None` for the first page, and returns the items on the page along with the token of the next
+// page, if there is one.
+type FetchPage<'a, T> =
+    Box<FnMut(Option<String>) -> Result<(Vec<T>, Option<String>), FleetError> + Send + 'a>;
+
+/// An iterator over every item of a paginated resource in the fleet cluster.
+///
+/// Pages are requested lazily as the iterator is advanced. If a request fails, the error is
+/// yielded in place of the next item and iteration ends.
+pub struct PageIter<'a, T> {
+    fetch: FetchPage<'a, T>,
+    items: vec::IntoIter<T>,
+    next_page_token: Option<String>,
+    done: bool,
+}
+
+/// An iterator over every machine in the fleet cluster, returned by `Client::machines`.
+pub type MachineIter<'a> = PageIter<'a, Machine>;
+
+/// An iterator over every unit in the fleet cluster, returned by `Client::units`.
+pub type UnitIter<'a> = PageIter<'a, Unit>;
+
+/// An iterator over the states of units in the fleet cluster, returned by `Client::unit_states`.
+pub type UnitStateIter<'a> = PageIter<'a, UnitState>;
+
+impl<'a, T> PageIter<'a, T> {
+    /// Constructs a new `PageIter` that requests each page with `fetch`.
+    pub fn new<F>(fetch: F) -> PageIter<'a, T>
+    where F: FnMut(Option<String>) -> Result<(Vec<T>, Option<String>), FleetError> + Send + 'a {
+        PageIter {
+            fetch: Box::new(fetch),
+            items: vec![].into_iter(),
+            next_page_token: None,
+            done: false,
+        }
+    }
+}
+
+impl<'a, T> Iterator for PageIter<'a, T> {
+    type Item = Result<T, FleetError>;
+
+    fn next(&mut self) -> Option<Result<T, FleetError>> {
+        loop {
+            if let Some(item) = self.items.next() {
+                return Some(Ok(item));
+            }
+
+            if self.done {
+                return None;
+            }
+
+            match (self.fetch)(self.next_page_token.take()) {
+                Ok((items, next_page_token)) => {
+                    self.done = next_page_token.is_none();
+                    self.next_page_token = next_page_token;
+                    self.items = items.into_iter();
+                },
+                Err(error) => {
+                    self.done = true;
+
+                    return Some(Err(error));
+                },
+            }
+        }
+    }
+}
@@ -1,25 +1,79 @@
 //! Crate fleet provides a client for the [fleet](https://github.com/coreos/fleet) API.
 //!
 //! All of the public types are rexported and available directly from the crate root. `Client` is
-//! the entry point for all API calls.
+//! the entry point for all API calls. Exporting and importing cluster snapshots lives in the
+//! `snapshot` module.
+extern crate futures;
+extern crate futures_cpupool;
 extern crate hyper;
+extern crate openssl;
 extern crate rustc_serialize;
+extern crate sha1;
 extern crate url;
 
+pub use async_client::{AsyncClient, FleetFuture};
+pub use auth::Credentials;
+pub use builder::ClientBuilder;
 pub use client::Client;
-pub use error::FleetError;
+pub use error::{
+    ApiError,
+    ApiErrorKind,
+    DecodeError,
+    FleetError,
+    SnapshotError,
+    TimeoutKind,
+    TlsError,
+    WaitTimeout
+};
+#[cfg(feature = "fake")]
+pub use fake::FakeFleet;
+pub use iter::{MachineIter, PageIter, UnitIter, UnitStateIter};
+pub use pool::PoolConfig;
+pub use reconcile::{Change, DesiredUnit, Plan};
+pub use retry::RetryPolicy;
 pub use schema::{
+    parse_unit_file,
+    render_unit_file,
+    unit_hash,
+    FleetScheduling,
     Machine,
     MachinePage,
+    SchedulingError,
     Unit,
+    UnitFileError,
+    UnitName,
+    UnitNameError,
     UnitOption,
     UnitPage,
     UnitState,
     UnitStatePage,
     UnitStates
 };
+pub use simulator::{simulate, Exclusion, ScheduledUnit, Simulation};
+pub use snapshot::{Conflict, ImportOptions, Snapshot};
+pub use transport::{HyperTransport, Method, Request, Response, Transport};
+pub use wait::WaitOptions;
+pub use watch::{UnitEvent, Watcher};
 
+mod async_client;
+mod auth;
+mod builder;
 mod client;
+mod connector;
+mod endpoint;
 mod error;
+#[cfg(feature = "fake")]
+mod fake;
+mod glob;
+mod iter;
+mod pool;
+mod reconcile;
+mod retry;
 mod schema;
 mod serialize;
+mod simulator;
+pub mod snapshot;
+mod tls;
+mod transport;
+mod wait;
+mod watch;
@@ -0,0 +1,472 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use hyper::net::NetworkStream;
+
+/// Configuration for the keep-alive connection pool shared by every request a `Client` makes.
+pub struct PoolConfig {
+    /// The maximum number of idle connections kept open for each host.
+    pub max_idle: usize,
+    /// How long a connection may sit unused in the pool before it is closed instead of reused.
+    pub idle_timeout: Duration,
+}
+
+impl Default for PoolConfig {
+    fn default() -> PoolConfig {
+        PoolConfig {
+            max_idle: 5,
+            idle_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// A connection that can be kept in a `Pool` between requests. Not intended for public use.
+pub trait Reusable {
+    /// Returns whether the connection is still open with nothing waiting to be read, i.e. the peer
+    /// has not closed it or sent anything since the last response was read. The connection must
+    /// not be blocked by the check.
+    fn is_reusable(&mut self) -> bool;
+}
+
+// The most bytes of a response head a `PooledStream` keeps to decide whether the connection can be
+// reused. Connections with longer response heads are closed afterwards.
+const MAX_HEAD_LENGTH: usize = 16 * 1024;
+
+type Key = (String, u16, String);
+
+struct IdleStream<S> {
+    stream: S,
+    idle_since: Instant,
+}
+
+struct PoolState<S> {
+    config: PoolConfig,
+    idle: HashMap<Key, Vec<IdleStream<S>>>,
+}
+
+impl<S: Reusable> PoolState<S> {
+    fn checkout(&mut self, key: &Key) -> Option<S> {
+        let idle_timeout = self.config.idle_timeout;
+
+        let streams = match self.idle.get_mut(key) {
+            Some(streams) => streams,
+            None => return None,
+        };
+
+        streams.retain(|idle| idle.idle_since.elapsed() < idle_timeout);
+
+        // fleetd may have closed connections that sat idle for a while, which would only be noticed
+        // once a request had been written to them.
+        while let Some(mut idle) = streams.pop() {
+            if idle.stream.is_reusable() {
+                return Some(idle.stream);
+            }
+        }
+
+        None
+    }
+}
+
+impl<S> PoolState<S> {
+    fn checkin(&mut self, key: Key, stream: S) {
+        let max_idle = self.config.max_idle;
+        let streams = self.idle.entry(key).or_insert(vec![]);
+
+        if streams.len() < max_idle {
+            streams.push(IdleStream {
+                stream: stream,
+                idle_since: Instant::now(),
+            });
+        }
+    }
+}
+
+/// Keep-alive connections shared by every request a `HyperTransport` sends, which hands out a
+/// previously opened connection when one is available for the requested host. The pool is only
+/// locked while a connection is checked out or returned, so requests on different connections
+/// proceed in parallel.
+pub struct Pool<S> {
+    state: Arc<Mutex<PoolState<S>>>,
+}
+
+impl<S: Reusable> Pool<S> {
+    pub fn new(config: PoolConfig) -> Pool<S> {
+        Pool {
+            state: Arc::new(Mutex::new(PoolState {
+                config: config,
+                idle: HashMap::new(),
+            })),
+        }
+    }
+
+    /// Checks out an idle connection to the given host, or opens a new one with `open` if there
+    /// is none.
+    pub fn connect<F>(
+        &self,
+        host: &str,
+        port: u16,
+        scheme: &str,
+        open: F,
+    ) -> io::Result<PooledStream<S>> where F: FnOnce() -> io::Result<S> {
+        let key = (host.to_string(), port, scheme.to_string());
+        let pooled = self.state.lock().unwrap().checkout(&key);
+
+        let stream = match pooled {
+            Some(stream) => stream,
+            None => try!(open()),
+        };
+
+        Ok(PooledStream {
+            inner: Some((key, stream)),
+            closed: false,
+            head: vec![],
+            head_complete: false,
+            state: self.state.clone(),
+        })
+    }
+}
+
+/// A connection checked out of a `Pool`.
+///
+/// It is returned to the pool when dropped, but only if a complete response head was read from it
+/// and that response lets the connection be kept alive. Connections are closed instead if the
+/// response was HTTP/1.0 or had a `Connection: close` header, if the peer closed them, or if an
+/// I/O error occurred while they were in use.
+pub struct PooledStream<S> {
+    inner: Option<(Key, S)>,
+    closed: bool,
+    head: Vec<u8>,
+    head_complete: bool,
+    state: Arc<Mutex<PoolState<S>>>,
+}
+
+impl<S> PooledStream<S> {
+    /// Returns the underlying connection.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner.as_mut().unwrap().1
+    }
+
+    // Collects the bytes of the response head as they are read, and decides whether the
+    // connection can be reused once all of it has arrived.
+    fn inspect(&mut self, bytes: &[u8]) {
+        if self.head_complete {
+            return;
+        }
+
+        self.head.extend_from_slice(bytes);
+
+        let end = match self.head.windows(4).position(|window| window == b"\r\n\r\n") {
+            Some(end) => end,
+            None => {
+                if self.head.len() > MAX_HEAD_LENGTH {
+                    self.closed = true;
+                    self.head_complete = true;
+                    self.head = vec![];
+                }
+
+                return;
+            },
+        };
+
+        if !keeps_alive(&self.head[..end]) {
+            self.closed = true;
+        }
+
+        self.head_complete = true;
+        self.head = vec![];
+    }
+}
+
+// Returns whether the response with the given head allows the connection to be used for another
+// request.
+fn keeps_alive(head: &[u8]) -> bool {
+    let head = String::from_utf8_lossy(head);
+    let mut lines = head.split("\r\n");
+
+    if !lines.next().map_or(false, |status| status.starts_with("HTTP/1.1 ")) {
+        return false;
+    }
+
+    !lines.any(|line| {
+        let mut parts = line.splitn(2, ':');
+        let name = parts.next().unwrap_or("").trim();
+        let value = parts.next().unwrap_or("");
+
+        name.to_lowercase() == "connection" && value.split(',').any(|token| {
+            token.trim().to_lowercase() == "close"
+        })
+    })
+}
+
+impl<S: NetworkStream> Read for PooledStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let result = self.inner.as_mut().unwrap().1.read(buf);
+
+        match result {
+            Ok(0) if buf.len() > 0 => self.closed = true,
+            Ok(read) => self.inspect(&buf[..read]),
+            Err(_) => self.closed = true,
+        }
+
+        result
+    }
+}
+
+impl<S: NetworkStream> Write for PooledStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = self.inner.as_mut().unwrap().1.write(buf);
+
+        if result.is_err() {
+            self.closed = true;
+        }
+
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let result = self.inner.as_mut().unwrap().1.flush();
+
+        if result.is_err() {
+            self.closed = true;
+        }
+
+        result
+    }
+}
+
+impl<S: NetworkStream> NetworkStream for PooledStream<S> {
+    fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+        self.inner.as_mut().unwrap().1.peer_addr()
+    }
+}
+
+impl<S> Drop for PooledStream<S> {
+    fn drop(&mut self) {
+        if self.closed || !self.head_complete {
+            return;
+        }
+
+        if let Some((key, stream)) = self.inner.take() {
+            if let Ok(mut state) = self.state.lock() {
+                state.checkin(key, stream);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod pool_tests {
+    use std::io::{self, Cursor, Read, Write};
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    use hyper::net::NetworkStream;
+
+    use super::{Pool, PoolConfig, PooledStream, Reusable};
+
+    const KEEP_ALIVE: &'static [u8] = b"HTTP/1.1 204 No Content\r\n\r\n";
+
+    struct MockStream {
+        read: Cursor<Vec<u8>>,
+        reusable: bool,
+    }
+
+    impl Read for MockStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.read.read(buf)
+        }
+    }
+
+    impl Write for MockStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl NetworkStream for MockStream {
+        fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+            Ok("127.0.0.1:2999".parse().unwrap())
+        }
+    }
+
+    impl Reusable for MockStream {
+        fn is_reusable(&mut self) -> bool {
+            self.reusable
+        }
+    }
+
+    struct MockConnector {
+        connects: usize,
+        response: &'static [u8],
+        reusable: bool,
+    }
+
+    impl MockConnector {
+        fn new(response: &'static [u8]) -> MockConnector {
+            MockConnector {
+                connects: 0,
+                response: response,
+                reusable: true,
+            }
+        }
+
+        fn connect(&mut self) -> io::Result<MockStream> {
+            self.connects += 1;
+
+            Ok(MockStream {
+                read: Cursor::new(self.response.to_vec()),
+                reusable: self.reusable,
+            })
+        }
+    }
+
+    fn connect(
+        pool: &Pool<MockStream>,
+        connector: &mut MockConnector,
+        port: u16,
+    ) -> PooledStream<MockStream> {
+        pool.connect("localhost", port, "http", || connector.connect()).unwrap()
+    }
+
+    // Reads the response the mock connection sends, as a new response each time, without reading
+    // past its end.
+    fn read_response(stream: &mut PooledStream<MockStream>) {
+        let length = {
+            let read = &mut stream.inner.as_mut().unwrap().1.read;
+            read.set_position(0);
+            read.get_ref().len()
+        };
+        let mut response = vec![0; length];
+
+        stream.read_exact(&mut response).unwrap();
+    }
+
+    fn request(pool: &Pool<MockStream>, connector: &mut MockConnector, port: u16) {
+        let mut stream = connect(pool, connector, port);
+
+        read_response(&mut stream);
+    }
+
+    #[test]
+    fn it_reuses_idle_connections() {
+        let pool = Pool::new(PoolConfig::default());
+        let mut connector = MockConnector::new(KEEP_ALIVE);
+
+        request(&pool, &mut connector, 2999);
+        request(&pool, &mut connector, 2999);
+
+        assert_eq!(connector.connects, 1);
+    }
+
+    #[test]
+    fn it_keeps_connections_to_different_hosts_apart() {
+        let pool = Pool::new(PoolConfig::default());
+        let mut connector = MockConnector::new(KEEP_ALIVE);
+
+        request(&pool, &mut connector, 2999);
+        request(&pool, &mut connector, 3000);
+
+        assert_eq!(connector.connects, 2);
+    }
+
+    #[test]
+    fn it_does_not_reuse_closed_connections() {
+        let pool = Pool::new(PoolConfig::default());
+        let mut connector = MockConnector::new(KEEP_ALIVE);
+
+        {
+            let mut stream = connect(&pool, &mut connector, 2999);
+            let mut body = vec![];
+            stream.read_to_end(&mut body).unwrap();
+        }
+
+        request(&pool, &mut connector, 2999);
+
+        assert_eq!(connector.connects, 2);
+    }
+
+    #[test]
+    fn it_does_not_reuse_connections_without_a_complete_response() {
+        let pool = Pool::new(PoolConfig::default());
+        let mut connector = MockConnector::new(KEEP_ALIVE);
+
+        drop(connect(&pool, &mut connector, 2999));
+        request(&pool, &mut connector, 2999);
+
+        assert_eq!(connector.connects, 2);
+    }
+
+    #[test]
+    fn it_does_not_reuse_connections_the_server_asks_to_close() {
+        let responses: [&'static [u8]; 3] = [
+            b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n",
+            b"HTTP/1.1 204 No Content\r\nconnection: Upgrade, Close\r\n\r\n",
+            b"HTTP/1.0 204 No Content\r\n\r\n",
+        ];
+
+        for response in responses.iter() {
+            let pool = Pool::new(PoolConfig::default());
+            let mut connector = MockConnector::new(response);
+
+            request(&pool, &mut connector, 2999);
+            request(&pool, &mut connector, 2999);
+
+            assert_eq!(connector.connects, 2);
+        }
+    }
+
+    #[test]
+    fn it_does_not_reuse_connections_closed_while_idle() {
+        let mut connector = MockConnector::new(KEEP_ALIVE);
+        connector.reusable = false;
+
+        let pool = Pool::new(PoolConfig::default());
+
+        request(&pool, &mut connector, 2999);
+        request(&pool, &mut connector, 2999);
+
+        assert_eq!(connector.connects, 2);
+    }
+
+    #[test]
+    fn it_does_not_reuse_expired_connections() {
+        let config = PoolConfig { max_idle: 5, idle_timeout: Duration::from_secs(0) };
+        let pool = Pool::new(config);
+        let mut connector = MockConnector::new(KEEP_ALIVE);
+
+        request(&pool, &mut connector, 2999);
+        request(&pool, &mut connector, 2999);
+
+        assert_eq!(connector.connects, 2);
+    }
+
+    #[test]
+    fn it_respects_max_idle() {
+        let config = PoolConfig { max_idle: 1, idle_timeout: Duration::from_secs(30) };
+        let pool = Pool::new(config);
+        let mut connector = MockConnector::new(KEEP_ALIVE);
+
+        let mut first = connect(&pool, &mut connector, 2999);
+        let mut second = connect(&pool, &mut connector, 2999);
+        read_response(&mut first);
+        read_response(&mut second);
+        drop(first);
+        drop(second);
+
+        let mut third = connect(&pool, &mut connector, 2999);
+        let mut fourth = connect(&pool, &mut connector, 2999);
+        read_response(&mut third);
+        read_response(&mut fourth);
+        drop(third);
+        drop(fourth);
+
+        assert_eq!(connector.connects, 3);
+    }
+}
@@ -0,0 +1,374 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use client::Client;
+use error::FleetError;
+use schema::{unit_hash, FleetScheduling, Unit, UnitName, UnitOption, UnitStates};
+
+/// A unit as it should exist in the fleet cluster, for use with `Client::plan`.
+#[derive(Clone, Debug)]
+pub struct DesiredUnit {
+    /// The unit's name.
+    pub name: String,
+    /// The lines of key/value pairs that make up the unit file.
+    pub options: Vec<UnitOption>,
+    /// The state fleet should move the unit into.
+    pub desired_state: UnitStates,
+}
+
+/// A single change in a `Plan`.
+#[derive(Clone, Debug)]
+pub enum Change {
+    /// Create a unit that does not exist yet.
+    Create(DesiredUnit),
+    /// Destroy a unit that is not desired.
+    Destroy(String),
+    /// Change the desired state of a unit whose contents are already as desired.
+    SetState {
+        /// The unit's name.
+        name: String,
+        /// The unit's desired state before the change.
+        from: UnitStates,
+        /// The unit's desired state after the change.
+        to: UnitStates,
+    },
+    /// Destroy a unit whose contents differ from the desired ones, then create it again. fleet
+    /// units are immutable, so this is the only way to change their contents.
+    Replace {
+        /// The unit as it will be created.
+        unit: DesiredUnit,
+        /// The hash of the unit's current contents.
+        previous_hash: String,
+    },
+}
+
+impl Change {
+    // Checks what fleet would reject before any request is sent: the unit's name and, for units
+    // that are created, their `[X-Fleet]` section.
+    fn validate(&self) -> Result<(), FleetError> {
+        try!(UnitName::parse(change_name(self)));
+
+        match *self {
+            Change::Create(ref unit) | Change::Replace { ref unit, .. } => {
+                try!(FleetScheduling::from_options(&unit.options));
+            },
+            Change::Destroy(_) | Change::SetState { .. } => {},
+        }
+
+        Ok(())
+    }
+
+    fn apply(&self, client: &Client) -> Result<(), FleetError> {
+        match *self {
+            Change::Create(ref unit) => {
+                client.create_unit(&unit.name, unit.desired_state, unit.options.clone())
+            },
+            Change::Destroy(ref name) => client.destroy_unit(name),
+            Change::SetState { ref name, to, .. } => client.modify_unit(name, to),
+            Change::Replace { ref unit, .. } => {
+                try!(client.destroy_unit(&unit.name));
+
+                client.create_unit(&unit.name, unit.desired_state, unit.options.clone())
+            },
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        match *self {
+            Change::Create(_) => "+",
+            Change::Destroy(_) => "-",
+            Change::SetState { .. } => "~",
+            Change::Replace { .. } => "-/+",
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Change::Create(ref unit) => {
+                write!(f, "create {} ({})", unit.name, unit.desired_state)
+            },
+            Change::Destroy(ref name) => write!(f, "destroy {}", name),
+            Change::SetState { ref name, from, to } => {
+                write!(f, "change the desired state of {} from {} to {}", name, from, to)
+            },
+            Change::Replace { ref unit, ref previous_hash } => {
+                write!(
+                    f,
+                    "replace {} ({}, contents {} => {})",
+                    unit.name,
+                    unit.desired_state,
+                    short_hash(previous_hash),
+                    short_hash(&unit_hash(&unit.options))
+                )
+            },
+        }
+    }
+}
+
+/// The changes needed to bring the units in the fleet cluster in line with a desired set of
+/// units, returned by `Client::plan`.
+///
+/// Units that exist but are not desired are destroyed, units that are desired but do not exist
+/// are created, and units whose contents differ are replaced. Units whose contents match but whose
+/// desired state differs only have their state changed. Changes are ordered so that destroys come
+/// first, then replacements, creations and state changes, each sorted by unit name.
+///
+/// A plan's `Display` output lists its changes, one per line, followed by a summary.
+#[derive(Clone, Debug)]
+pub struct Plan {
+    /// The changes, in the order they will be applied.
+    pub changes: Vec<Change>,
+}
+
+impl Plan {
+    /// Constructs the plan that turns the units `current` into the units `desired`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fleet::{DesiredUnit, Plan, UnitOption, UnitStates};
+    ///
+    /// let desired = vec![
+    ///     DesiredUnit {
+    ///         name: "web.service".to_string(),
+    ///         options: vec![
+    ///             UnitOption {
+    ///                 name: "ExecStart".to_string(),
+    ///                 section: "Service".to_string(),
+    ///                 value: "/usr/bin/web".to_string(),
+    ///             },
+    ///         ],
+    ///         desired_state: UnitStates::Launched,
+    ///     },
+    /// ];
+    ///
+    /// let plan = Plan::new(&desired, &[]);
+    ///
+    /// assert_eq!(
+    ///     plan.to_string(),
+    ///     "+ create web.service (launched)\nPlan: 1 to create, 0 to replace, 0 to change, \
+    ///      0 to destroy.\n"
+    /// );
+    /// ```
+    pub fn new(desired: &[DesiredUnit], current: &[Unit]) -> Plan {
+        let current_by_name: HashMap<&str, &Unit> = current.iter().map(|unit| {
+            (&unit.name[..], unit)
+        }).collect();
+        let desired_by_name: HashMap<&str, &DesiredUnit> = desired.iter().map(|unit| {
+            (&unit.name[..], unit)
+        }).collect();
+
+        let mut destroys = vec![];
+        let mut replacements = vec![];
+        let mut creations = vec![];
+        let mut state_changes = vec![];
+
+        for unit in current.iter() {
+            if !desired_by_name.contains_key(&unit.name[..]) {
+                destroys.push(Change::Destroy(unit.name.clone()));
+            }
+        }
+
+        for unit in desired.iter() {
+            let existing = match current_by_name.get(&unit.name[..]) {
+                Some(existing) => existing,
+                None => {
+                    creations.push(Change::Create(unit.clone()));
+
+                    continue;
+                },
+            };
+
+            let previous_hash = existing.hash();
+
+            if previous_hash != unit_hash(&unit.options) {
+                replacements.push(Change::Replace {
+                    unit: unit.clone(),
+                    previous_hash: previous_hash,
+                });
+            } else if existing.desired_state != unit.desired_state {
+                state_changes.push(Change::SetState {
+                    name: unit.name.clone(),
+                    from: existing.desired_state,
+                    to: unit.desired_state,
+                });
+            }
+        }
+
+        let mut changes = vec![];
+
+        for mut group in vec![destroys, replacements, creations, state_changes] {
+            group.sort_by(|a, b| change_name(a).cmp(change_name(b)));
+            changes.extend(group);
+        }
+
+        Plan {
+            changes: changes,
+        }
+    }
+
+    /// Returns whether the cluster already matches the desired units.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Checks every change, then applies the changes in order using `client`. Returns a
+    /// description of each change applied.
+    ///
+    /// Before anything is sent, each unit's name is checked and the `[X-Fleet]` section of each
+    /// unit to be created is parsed and validated, so a plan fleet would reject partway through
+    /// fails without changing the cluster. If `dry_run` is true, the changes are checked and
+    /// described but no requests are sent.
+    ///
+    /// # Failures
+    ///
+    /// Returns a `FleetError::Apply` describing the first change that is invalid or fails. If a
+    /// change fails once requests have been sent, the changes before it remain applied.
+    pub fn apply(&self, client: &Client, dry_run: bool) -> Result<Vec<String>, FleetError> {
+        for change in self.changes.iter() {
+            if let Err(error) = change.validate() {
+                return Err(FleetError::Apply(change.to_string(), Box::new(error)));
+            }
+        }
+
+        let descriptions = self.changes.iter().map(|change| change.to_string()).collect();
+
+        if dry_run {
+            return Ok(descriptions);
+        }
+
+        for change in self.changes.iter() {
+            if let Err(error) = change.apply(client) {
+                return Err(FleetError::Apply(change.to_string(), Box::new(error)));
+            }
+        }
+
+        Ok(descriptions)
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No changes. The units in the cluster match the desired units.");
+        }
+
+        let mut counts = [0; 4];
+
+        for change in self.changes.iter() {
+            try!(writeln!(f, "{} {}", change.symbol(), change));
+
+            let index = match *change {
+                Change::Create(_) => 0,
+                Change::Replace { .. } => 1,
+                Change::SetState { .. } => 2,
+                Change::Destroy(_) => 3,
+            };
+
+            counts[index] += 1;
+        }
+
+        writeln!(
+            f,
+            "Plan: {} to create, {} to replace, {} to change, {} to destroy.",
+            counts[0],
+            counts[1],
+            counts[2],
+            counts[3]
+        )
+    }
+}
+
+fn change_name(change: &Change) -> &str {
+    match *change {
+        Change::Create(ref unit) | Change::Replace { ref unit, .. } => &unit.name,
+        Change::Destroy(ref name) | Change::SetState { ref name, .. } => name,
+    }
+}
+
+fn short_hash(hash: &str) -> &str {
+    if hash.len() > 7 { &hash[..7] } else { hash }
+}
+
+#[cfg(test)]
+mod plan_tests {
+    use super::{Change, DesiredUnit, Plan};
+    use schema::{Unit, UnitOption, UnitStates};
+
+    fn options(command: &str) -> Vec<UnitOption> {
+        vec![
+            UnitOption {
+                name: "ExecStart".to_string(),
+                section: "Service".to_string(),
+                value: command.to_string(),
+            },
+        ]
+    }
+
+    fn desired(name: &str, command: &str, desired_state: UnitStates) -> DesiredUnit {
+        DesiredUnit {
+            name: name.to_string(),
+            options: options(command),
+            desired_state: desired_state,
+        }
+    }
+
+    fn current(name: &str, command: &str, desired_state: UnitStates) -> Unit {
+        Unit {
+            current_state: desired_state,
+            desired_state: desired_state,
+            machine_id: None,
+            name: name.to_string(),
+            options: options(command),
+        }
+    }
+
+    #[test]
+    fn it_plans_nothing_when_the_cluster_matches() {
+        let plan = Plan::new(
+            &[desired("a.service", "/bin/a", UnitStates::Launched)],
+            &[current("a.service", "/bin/a", UnitStates::Launched)]
+        );
+
+        assert!(plan.is_empty());
+        assert_eq!(
+            plan.to_string(),
+            "No changes. The units in the cluster match the desired units.\n"
+        );
+    }
+
+    #[test]
+    fn it_plans_every_kind_of_change() {
+        let plan = Plan::new(
+            &[
+                desired("state.service", "/bin/state", UnitStates::Launched),
+                desired("new.service", "/bin/new", UnitStates::Loaded),
+                desired("changed.service", "/bin/changed --v2", UnitStates::Launched),
+            ],
+            &[
+                current("state.service", "/bin/state", UnitStates::Loaded),
+                current("changed.service", "/bin/changed", UnitStates::Launched),
+                current("old.service", "/bin/old", UnitStates::Launched),
+            ]
+        );
+
+        let changes: Vec<String> = plan.changes.iter().map(|change| {
+            match *change {
+                Change::Replace { ref unit, .. } => format!("replace {}", unit.name),
+                ref change => change.to_string(),
+            }
+        }).collect();
+
+        assert_eq!(changes, vec![
+            "destroy old.service".to_string(),
+            "replace changed.service".to_string(),
+            "create new.service (loaded)".to_string(),
+            "change the desired state of state.service from loaded to launched".to_string(),
+        ]);
+        assert!(plan.to_string().ends_with(
+            "Plan: 1 to create, 1 to replace, 1 to change, 1 to destroy.\n"
+        ));
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::time::Duration;
+
+use error::{FleetError, TimeoutKind};
+use transport::{Method, Response};
+
+/// Configuration for retrying requests that fail for transient reasons, such as fleet returning
+/// a 503 while it elects a new leader.
+///
+/// A failed request is retried if its result is retryable under the policy and fewer than
+/// `max_attempts` attempts have been made. Before each retry, `Client` waits for an exponentially
+/// increasing delay: `base_delay` before the first retry, twice that before the second, and so
+/// on, up to `max_delay`. With `jitter` enabled, each delay is instead chosen at random between
+/// half and all of that amount, so that many clients retrying at once do not all hit fleet at the
+/// same moment.
+///
+/// The default policy makes up to 3 attempts, starting with a 100 millisecond delay. A `Client`
+/// only retries requests when given a policy with `ClientBuilder::retry_policy`.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use fleet::{ClientBuilder, RetryPolicy};
+///
+/// let policy = RetryPolicy {
+///     max_attempts: 5,
+///     max_delay: Duration::from_secs(2),
+///     ..RetryPolicy::default()
+/// };
+///
+/// let client = ClientBuilder::new("http://localhost:2999")
+///     .retry_policy(policy)
+///     .build()
+///     .ok()
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// The maximum number of times a request is attempted, including the first attempt. A value
+    /// of 1 disables retries.
+    pub max_attempts: u32,
+    /// The delay before the first retry.
+    pub base_delay: Duration,
+    /// The longest delay between two attempts.
+    pub max_delay: Duration,
+    /// Whether delays are randomized.
+    pub jitter: bool,
+    /// The HTTP status codes returned by fleet that cause a request to be retried.
+    pub retryable_statuses: Vec<u16>,
+    /// Whether requests that fail with a `FleetError::Transport`, such as a refused or reset
+    /// connection, are retried.
+    pub retry_transport_errors: bool,
+    /// The timeouts whose expiry causes a request to be retried.
+    pub retryable_timeouts: Vec<TimeoutKind>,
+    /// Whether PUT requests, which create and modify units, are retried. A PUT that failed may
+    /// still have taken effect, so these are not retried unless this is set, or unless the PUT
+    /// was never sent because no connection to fleet could be opened.
+    pub retry_puts: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            jitter: true,
+            retryable_statuses: vec![500, 502, 503, 504],
+            retry_transport_errors: true,
+            retryable_timeouts: vec![TimeoutKind::Connect, TimeoutKind::Read, TimeoutKind::Write],
+            retry_puts: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns a policy that never retries requests.
+    pub fn never() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        }
+    }
+}
+
+/// Returns whether `policy` retries a request made with `method` that produced `result` on attempt
+/// number `attempt`, starting at 1.
+pub fn should_retry(
+    policy: &RetryPolicy,
+    method: Method,
+    result: &Result<Response, FleetError>,
+    attempt: u32,
+) -> bool {
+    if attempt >= policy.max_attempts {
+        return false;
+    }
+
+    // A PUT that reached fleet may have taken effect even though it failed.
+    let sent = !result.as_ref().err().map_or(false, never_sent);
+
+    if method == Method::Put && !policy.retry_puts && sent {
+        return false;
+    }
+
+    match *result {
+        Ok(ref response) => policy.retryable_statuses.contains(&response.status),
+        Err(FleetError::Transport(_)) => policy.retry_transport_errors,
+        Err(FleetError::Timeout(kind)) => policy.retryable_timeouts.contains(&kind),
+        Err(_) => false,
+    }
+}
+
+/// Returns whether `error` shows that a request was never sent, because no connection to fleet
+/// could be opened. Such a request is safe to send again even if it is a PUT.
+pub fn never_sent(error: &FleetError) -> bool {
+    match *error {
+        FleetError::Timeout(TimeoutKind::Connect) => true,
+        FleetError::Transport(ref error) => match error.downcast_ref::<io::Error>() {
+            Some(error) => error.kind() == io::ErrorKind::ConnectionRefused,
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+/// Returns how long `policy` waits before retrying a request that failed on attempt number
+/// `attempt`, starting at 1.
+pub fn delay(policy: &RetryPolicy, attempt: u32) -> Duration {
+    let mut delay = policy.base_delay;
+
+    for _ in 1..attempt {
+        if delay >= policy.max_delay {
+            break;
+        }
+
+        delay = delay * 2;
+    }
+
+    if delay > policy.max_delay {
+        delay = policy.max_delay;
+    }
+
+    if policy.jitter {
+        let half = delay / 2;
+        let nanos = half.as_secs() * 1_000_000_000 + half.subsec_nanos() as u64;
+
+        if nanos > 0 {
+            delay = half + nanos_to_duration(random() % (nanos + 1));
+        }
+    }
+
+    delay
+}
+
+fn nanos_to_duration(nanos: u64) -> Duration {
+    Duration::new(nanos / 1_000_000_000, (nanos % 1_000_000_000) as u32)
+}
+
+// Returns a random number, using the random keys the standard library generates for hash maps.
+fn random() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+#[cfg(test)]
+mod retry_policy_tests {
+    use std::io;
+    use std::time::Duration;
+
+    use super::{delay, should_retry, RetryPolicy};
+    use error::{FleetError, TimeoutKind};
+    use transport::{Method, Response};
+
+    fn response(status: u16) -> Result<Response, FleetError> {
+        Ok(Response {
+            status: status,
+            body: String::new(),
+        })
+    }
+
+    #[test]
+    fn it_retries_retryable_statuses() {
+        let policy = RetryPolicy::default();
+
+        assert!(should_retry(&policy, Method::Get, &response(503), 1));
+        assert!(!should_retry(&policy, Method::Get, &response(404), 1));
+        assert!(!should_retry(&policy, Method::Get, &response(200), 1));
+    }
+
+    #[test]
+    fn it_stops_after_the_maximum_number_of_attempts() {
+        let policy = RetryPolicy::default();
+
+        assert!(should_retry(&policy, Method::Get, &response(503), 2));
+        assert!(!should_retry(&policy, Method::Get, &response(503), 3));
+        assert!(!should_retry(&RetryPolicy::never(), Method::Get, &response(503), 1));
+    }
+
+    #[test]
+    fn it_only_retries_puts_when_allowed() {
+        let mut policy = RetryPolicy::default();
+
+        assert!(!should_retry(&policy, Method::Put, &response(503), 1));
+        assert!(should_retry(&policy, Method::Delete, &response(503), 1));
+
+        policy.retry_puts = true;
+
+        assert!(should_retry(&policy, Method::Put, &response(503), 1));
+    }
+
+    #[test]
+    fn it_retries_puts_that_were_never_sent() {
+        let policy = RetryPolicy::default();
+
+        let refused = io::Error::new(io::ErrorKind::ConnectionRefused, "connection refused");
+        let reset = io::Error::new(io::ErrorKind::ConnectionReset, "connection reset");
+
+        let refused = Err(FleetError::Transport(Box::new(refused)));
+        let reset = Err(FleetError::Transport(Box::new(reset)));
+        let connect = Err(FleetError::Timeout(TimeoutKind::Connect));
+        let read = Err(FleetError::Timeout(TimeoutKind::Read));
+
+        assert!(should_retry(&policy, Method::Put, &refused, 1));
+        assert!(should_retry(&policy, Method::Put, &connect, 1));
+        assert!(!should_retry(&policy, Method::Put, &reset, 1));
+        assert!(!should_retry(&policy, Method::Put, &read, 1));
+        assert!(!should_retry(&policy, Method::Put, &refused, 3));
+    }
+
+    #[test]
+    fn it_retries_selected_timeouts() {
+        let policy = RetryPolicy::default();
+
+        let read = Err(FleetError::Timeout(TimeoutKind::Read));
+        let request = Err(FleetError::Timeout(TimeoutKind::Request));
+
+        assert!(should_retry(&policy, Method::Get, &read, 1));
+        assert!(!should_retry(&policy, Method::Get, &request, 1));
+    }
+
+    #[test]
+    fn it_backs_off_exponentially_up_to_the_maximum_delay() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(350),
+            jitter: false,
+            ..RetryPolicy::default()
+        };
+
+        assert_eq!(delay(&policy, 1), Duration::from_millis(100));
+        assert_eq!(delay(&policy, 2), Duration::from_millis(200));
+        assert_eq!(delay(&policy, 3), Duration::from_millis(350));
+        assert_eq!(delay(&policy, 30), Duration::from_millis(350));
+    }
+
+    #[test]
+    fn it_jitters_delays_between_half_and_all_of_the_delay() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            ..RetryPolicy::default()
+        };
+
+        for _ in 0..100 {
+            let delay = delay(&policy, 2);
+
+            assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(200));
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use sha1::Sha1;
+
+use super::unit::UnitOption;
+use super::unit_file::serialize_unit_file;
+
+/// Computes the hash fleet assigns to a unit with the given options, as reported in
+/// `UnitState::hash`.
+///
+/// fleet hashes a unit by taking the SHA1 digest of its serialized unit file, so two sets of
+/// options have the same hash exactly when fleet would consider them the same unit. This makes it
+/// possible to check whether a local unit file matches what is running without comparing options
+/// one by one.
+///
+/// fleet serializes units with go-systemd, which writes every value verbatim, without escaping
+/// line breaks, so the text hashed is the one `render_unit_file` produces without its escaping.
+///
+/// # Examples
+///
+/// ```
+/// use fleet::{parse_unit_file, unit_hash};
+///
+/// let options = parse_unit_file("[Service]\nExecStart=/bin/sleep 100\n").unwrap();
+///
+/// assert_eq!(unit_hash(&options), "1c6fb6f3684bafb0c173d8b8b957ceff031180c1");
+/// ```
+pub fn unit_hash(options: &[UnitOption]) -> String {
+    let mut sha1 = Sha1::new();
+
+    sha1.update(serialize_unit_file(options).as_bytes());
+
+    sha1.digest().to_string()
+}
+
+#[cfg(test)]
+mod hash_tests {
+    use super::unit_hash;
+    use super::super::unit::UnitOption;
+    use super::super::unit_file::parse_unit_file;
+
+    // The unit and hash in fleet's own `TestUnitHash`, in unit/unit_test.go.
+    const FLEET_UNIT: &'static str = "[Service]\nExecStart=/bin/sleep 100\n";
+    const FLEET_HASH: &'static str = "1c6fb6f3684bafb0c173d8b8b957ceff031180c1";
+
+    #[test]
+    fn it_matches_fleets_hash_of_a_simple_unit() {
+        let options = vec![
+            UnitOption {
+                name: "ExecStart".to_string(),
+                section: "Service".to_string(),
+                value: "/bin/sleep 100".to_string(),
+            },
+        ];
+
+        assert_eq!(unit_hash(&options), FLEET_HASH);
+        assert_eq!(unit_hash(&parse_unit_file(FLEET_UNIT).unwrap()), FLEET_HASH);
+    }
+
+    #[test]
+    fn it_hashes_a_unit_without_options() {
+        // fleet serializes a unit without options to no bytes at all, and this is the SHA-1
+        // digest of empty input.
+        assert_eq!(unit_hash(&[]), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+    }
+
+    #[test]
+    fn it_hashes_the_options_rather_than_the_original_text() {
+        // fleet hashes the unit file it serializes from the parsed options, so comments, blank
+        // lines and sections without options do not change the hash.
+        let options = parse_unit_file("# A comment
+[Unit]
+
+[Service]
+ExecStart=/bin/sleep 100
+").unwrap();
+
+        assert_eq!(unit_hash(&options), FLEET_HASH);
+    }
+
+    #[test]
+    fn it_hashes_values_verbatim() {
+        let options = vec![
+            UnitOption {
+                name: "ExecStart".to_string(),
+                section: "Service".to_string(),
+                value: "/bin/echo one\n  two".to_string(),
+            },
+        ];
+        let escaped = vec![
+            UnitOption {
+                name: "ExecStart".to_string(),
+                section: "Service".to_string(),
+                value: "/bin/echo one\\\n  two".to_string(),
+            },
+        ];
+
+        assert!(unit_hash(&options) != unit_hash(&escaped));
+    }
+}
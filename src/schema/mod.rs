@@ -1,5 +1,13 @@
+pub use self::hash::unit_hash;
 pub use self::machine::{Machine, MachinePage};
+pub use self::scheduling::{FleetScheduling, SchedulingError};
 pub use self::unit::{Unit, UnitOption, UnitPage, UnitState, UnitStatePage, UnitStates};
+pub use self::unit_file::{parse_unit_file, render_unit_file, UnitFileError};
+pub use self::unit_name::{UnitName, UnitNameError};
 
+mod hash;
 mod machine;
+mod scheduling;
 mod unit;
+mod unit_file;
+mod unit_name;
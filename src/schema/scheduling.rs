@@ -0,0 +1,526 @@
+use std::error::Error;
+use std::fmt;
+
+use glob;
+use super::unit::UnitOption;
+
+/// An error describing an invalid `[X-Fleet]` section.
+#[derive(Debug)]
+pub struct SchedulingError {
+    /// A message describing the error.
+    pub message: String,
+}
+
+impl SchedulingError {
+    fn new(message: String) -> SchedulingError {
+        SchedulingError {
+            message: message,
+        }
+    }
+}
+
+impl fmt::Display for SchedulingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for SchedulingError {
+    fn description(&self) -> &str {
+        &self.message[..]
+    }
+}
+
+/// The scheduling requirements of a unit, from the `[X-Fleet]` section of its unit file.
+///
+/// fleet uses these options to decide which machines a unit may be scheduled to. Each field
+/// corresponds to one option:
+///
+/// * `MachineID` schedules the unit to the machine with the given ID.
+/// * `MachineOf` schedules the unit to the same machine as each of the given units.
+/// * `MachineMetadata` limits the unit to machines with the given metadata. Machines must match
+///   every key, and may match any of the values given for a key.
+/// * `Conflicts` keeps the unit off machines running units whose names match the given glob
+///   patterns.
+/// * `Global` schedules the unit to every eligible machine.
+/// * `Replaces` schedules the unit in place of each of the given units, moving them elsewhere.
+///
+/// # Examples
+///
+/// ```
+/// use fleet::FleetScheduling;
+///
+/// let scheduling = FleetScheduling::new()
+///     .machine_metadata("region", "us-east-1")
+///     .conflicts("apache@*.service");
+///
+/// assert!(scheduling.validate().is_ok());
+///
+/// let options = scheduling.to_options();
+///
+/// assert_eq!(options[0].name, "MachineMetadata");
+/// assert_eq!(options[0].value, "region=us-east-1");
+/// assert_eq!(options[1].name, "Conflicts");
+/// assert_eq!(options[1].value, "apache@*.service");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FleetScheduling {
+    /// The ID of the machine the unit must be scheduled to.
+    pub machine_id: Option<String>,
+    /// The names of units the unit must be scheduled alongside.
+    pub machine_of: Vec<String>,
+    /// Metadata key/value pairs a machine must have for the unit to be scheduled to it.
+    pub machine_metadata: Vec<(String, String)>,
+    /// Glob patterns matching the names of units the unit must not be scheduled alongside.
+    pub conflicts: Vec<String>,
+    /// Whether the unit is scheduled to every eligible machine.
+    pub global: bool,
+    /// The names of units the unit replaces on their machines.
+    pub replaces: Vec<String>,
+}
+
+impl FleetScheduling {
+    /// Constructs a `FleetScheduling` without any requirements.
+    pub fn new() -> FleetScheduling {
+        FleetScheduling::default()
+    }
+
+    /// Parses the `[X-Fleet]` section of a unit's options. Options in other sections are ignored.
+    ///
+    /// Values may list several items in quotes, e.g. `MachineMetadata="region=us-east-1"
+    /// "disk=ssd"`. The deprecated option names fleet still accepts are accepted too:
+    /// `X-ConditionMachineID` and `X-ConditionMachineBootID` for `MachineID`,
+    /// `X-ConditionMachineOf` for `MachineOf`, `X-ConditionMachineMetadata` for `MachineMetadata`
+    /// and `X-Conflicts` for `Conflicts`.
+    ///
+    /// As in fleet, `Global` is only true for the value `true`, in any case. Any other value
+    /// means false. If `Global` is given more than once, the last value wins.
+    ///
+    /// # Failures
+    ///
+    /// Returns a `SchedulingError` if the section contains an unknown option, if any value is
+    /// invalid, or if the requirements cannot be satisfied together. See `validate` for details.
+    pub fn from_options(options: &[UnitOption]) -> Result<FleetScheduling, SchedulingError> {
+        let mut scheduling = FleetScheduling::new();
+
+        for option in options.iter().filter(|option| option.section == "X-Fleet") {
+            let name = match DEPRECATED_NAMES.iter().find(|names| names.0 == option.name) {
+                Some(&(_, name)) => name,
+                None => &option.name[..],
+            };
+
+            for value in split_values(&option.value) {
+                match name {
+                    "MachineID" => {
+                        if scheduling.machine_id.is_some() {
+                            return Err(SchedulingError::new(
+                                "MachineID may only be given once".to_string()
+                            ));
+                        }
+
+                        scheduling.machine_id = Some(value);
+                    },
+                    "MachineOf" => scheduling.machine_of.push(value),
+                    "MachineMetadata" => {
+                        let pair = try!(parse_metadata(&value));
+
+                        scheduling.machine_metadata.push(pair);
+                    },
+                    "Conflicts" => scheduling.conflicts.push(value),
+                    "Global" => scheduling.global = value.to_lowercase() == "true",
+                    "Replaces" => scheduling.replaces.push(value),
+                    _ => return Err(SchedulingError::new(format!(
+                        "unknown option `{}` in [X-Fleet] section",
+                        option.name
+                    ))),
+                }
+            }
+        }
+
+        try!(scheduling.validate());
+
+        Ok(scheduling)
+    }
+
+    /// Returns the requirements as `[X-Fleet]` options, one option per value, in the order the
+    /// fields are declared. `Global` is only included when it is `true`.
+    pub fn to_options(&self) -> Vec<UnitOption> {
+        let mut options = vec![];
+
+        if let Some(ref machine_id) = self.machine_id {
+            options.push(fleet_option("MachineID", machine_id));
+        }
+
+        for unit in self.machine_of.iter() {
+            options.push(fleet_option("MachineOf", unit));
+        }
+
+        for &(ref key, ref value) in self.machine_metadata.iter() {
+            options.push(fleet_option("MachineMetadata", &format!("{}={}", key, value)));
+        }
+
+        for pattern in self.conflicts.iter() {
+            options.push(fleet_option("Conflicts", pattern));
+        }
+
+        if self.global {
+            options.push(fleet_option("Global", "true"));
+        }
+
+        for unit in self.replaces.iter() {
+            options.push(fleet_option("Replaces", unit));
+        }
+
+        options
+    }
+
+    /// Checks that the requirements are valid and can be satisfied together.
+    ///
+    /// # Failures
+    ///
+    /// Returns a `SchedulingError` if:
+    ///
+    /// * A machine ID or unit name is empty.
+    /// * A metadata key or value is empty or contains `=`.
+    /// * A `Conflicts` pattern is not a valid glob pattern.
+    /// * A unit listed in `MachineOf` also matches `Conflicts` or `Replaces`.
+    /// * Options are combined in a way fleet rejects: `MachineID` with `MachineOf`, `Conflicts`,
+    ///   `Replaces` or `Global`; `Global` with `MachineOf` or `Replaces`; or `Conflicts` with
+    ///   `Replaces`.
+    pub fn validate(&self) -> Result<(), SchedulingError> {
+        if let Some(ref machine_id) = self.machine_id {
+            if machine_id.is_empty() {
+                return Err(SchedulingError::new("MachineID must not be empty".to_string()));
+            }
+        }
+
+        for &(ref key, ref value) in self.machine_metadata.iter() {
+            try!(parse_metadata(&format!("{}={}", key, value)));
+        }
+
+        for unit in self.machine_of.iter().chain(self.replaces.iter()) {
+            if unit.is_empty() {
+                return Err(SchedulingError::new("unit names must not be empty".to_string()));
+            }
+        }
+
+        for pattern in self.conflicts.iter() {
+            if let Err(message) = glob::validate(pattern) {
+                return Err(SchedulingError::new(format!(
+                    "Conflicts value `{}` is invalid: {}",
+                    pattern,
+                    message
+                )));
+            }
+        }
+
+        for unit in self.machine_of.iter() {
+            for pattern in self.conflicts.iter() {
+                if glob::matches(pattern, unit) {
+                    return Err(SchedulingError::new(format!(
+                        "unresolvable requirements: MachineOf unit `{}` matches Conflicts `{}`",
+                        unit,
+                        pattern
+                    )));
+                }
+            }
+
+            if self.replaces.contains(unit) {
+                return Err(SchedulingError::new(format!(
+                    "unresolvable requirements: MachineOf unit `{}` is also replaced",
+                    unit
+                )));
+            }
+        }
+
+        let has_machine_id = self.machine_id.is_some();
+        let has_machine_of = !self.machine_of.is_empty();
+        let has_conflicts = !self.conflicts.is_empty();
+        let has_replaces = !self.replaces.is_empty();
+
+        let conflict = if has_machine_id && has_machine_of {
+            Some("MachineID cannot be used with MachineOf")
+        } else if has_machine_id && has_conflicts {
+            Some("MachineID cannot be used with Conflicts")
+        } else if has_machine_id && has_replaces {
+            Some("MachineID cannot be used with Replaces")
+        } else if has_machine_id && self.global {
+            Some("MachineID cannot be used with Global")
+        } else if self.global && has_machine_of {
+            Some("Global cannot be used with MachineOf")
+        } else if self.global && has_replaces {
+            Some("Global cannot be used with Replaces")
+        } else if has_conflicts && has_replaces {
+            Some("Conflicts cannot be used with Replaces")
+        } else {
+            None
+        };
+
+        match conflict {
+            Some(message) => Err(SchedulingError::new(message.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// Requires the unit to be scheduled to the machine with the given ID.
+    pub fn machine_id(mut self, machine_id: &str) -> FleetScheduling {
+        self.machine_id = Some(machine_id.to_string());
+        self
+    }
+
+    /// Requires the unit to be scheduled to the same machine as the given unit.
+    pub fn machine_of(mut self, unit: &str) -> FleetScheduling {
+        self.machine_of.push(unit.to_string());
+        self
+    }
+
+    /// Requires the unit to be scheduled to a machine with the given metadata.
+    pub fn machine_metadata(mut self, key: &str, value: &str) -> FleetScheduling {
+        self.machine_metadata.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Prevents the unit from being scheduled to a machine running a unit matching the given glob
+    /// pattern.
+    pub fn conflicts(mut self, pattern: &str) -> FleetScheduling {
+        self.conflicts.push(pattern.to_string());
+        self
+    }
+
+    /// Sets whether the unit is scheduled to every eligible machine.
+    pub fn global(mut self, global: bool) -> FleetScheduling {
+        self.global = global;
+        self
+    }
+
+    /// Schedules the unit in place of the given unit.
+    pub fn replaces(mut self, unit: &str) -> FleetScheduling {
+        self.replaces.push(unit.to_string());
+        self
+    }
+}
+
+// The deprecated option names fleet accepts, along with the names they stand for.
+const DEPRECATED_NAMES: [(&'static str, &'static str); 5] = [
+    ("X-ConditionMachineID", "MachineID"),
+    ("X-ConditionMachineBootID", "MachineID"),
+    ("X-ConditionMachineOf", "MachineOf"),
+    ("X-ConditionMachineMetadata", "MachineMetadata"),
+    ("X-Conflicts", "Conflicts"),
+];
+
+fn fleet_option(name: &str, value: &str) -> UnitOption {
+    UnitOption {
+        name: name.to_string(),
+        section: "X-Fleet".to_string(),
+        value: value.to_string(),
+    }
+}
+
+// Splits a value listing several quoted items separated by spaces, e.g. `"a=b" "c=d"`. Values not
+// wrapped in quotes are a single item. This mirrors how fleet reads multiple values from one line.
+fn split_values(value: &str) -> Vec<String> {
+    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return vec![value.to_string()];
+    }
+
+    let mut values = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in value.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            ' ' if !quoted => {
+                if !current.is_empty() {
+                    values.push(current);
+                    current = String::new();
+                }
+            },
+            _ => current.push(c),
+        }
+    }
+
+    values.push(current);
+
+    values
+}
+
+fn parse_metadata(value: &str) -> Result<(String, String), SchedulingError> {
+    let parts: Vec<&str> = value.split('=').collect();
+
+    if parts.len() != 2 || parts[0].is_empty() || parts[1].is_empty() {
+        return Err(SchedulingError::new(format!(
+            "MachineMetadata value `{}` must have the form `key=value`",
+            value
+        )));
+    }
+
+    Ok((parts[0].to_string(), parts[1].to_string()))
+}
+
+#[cfg(test)]
+mod scheduling_tests {
+    use super::FleetScheduling;
+    use super::super::unit_file::parse_unit_file;
+
+    fn parse(contents: &str) -> FleetScheduling {
+        FleetScheduling::from_options(&parse_unit_file(contents).unwrap()).unwrap()
+    }
+
+    fn parse_error(contents: &str) -> String {
+        FleetScheduling::from_options(&parse_unit_file(contents).unwrap()).err().unwrap().message
+    }
+
+    #[test]
+    fn it_parses_the_x_fleet_section() {
+        let scheduling = parse("[Service]
+ExecStart=/usr/bin/docker run coreos/apache
+
+[X-Fleet]
+MachineOf=database.service
+MachineMetadata=region=us-east-1
+MachineMetadata=disk=ssd
+");
+
+        assert_eq!(scheduling, FleetScheduling::new()
+            .machine_of("database.service")
+            .machine_metadata("region", "us-east-1")
+            .machine_metadata("disk", "ssd"));
+    }
+
+    #[test]
+    fn it_parses_quoted_multiple_values() {
+        let scheduling = parse(
+            "[X-Fleet]\nMachineMetadata=\"region=us-east-1\" \"region=us-west-1\"\n"
+        );
+
+        assert_eq!(scheduling.machine_metadata, vec![
+            ("region".to_string(), "us-east-1".to_string()),
+            ("region".to_string(), "us-west-1".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn it_parses_deprecated_option_names() {
+        let scheduling = parse("[X-Fleet]
+X-ConditionMachineOf=database.service
+X-ConditionMachineMetadata=region=us-east-1
+X-Conflicts=apache@*.service
+");
+
+        assert_eq!(scheduling, FleetScheduling::new()
+            .machine_of("database.service")
+            .machine_metadata("region", "us-east-1")
+            .conflicts("apache@*.service"));
+        assert_eq!(
+            parse("[X-Fleet]\nX-ConditionMachineID=abc123\n").machine_id,
+            Some("abc123".to_string())
+        );
+        assert_eq!(
+            parse("[X-Fleet]\nX-ConditionMachineBootID=abc123\n").machine_id,
+            Some("abc123".to_string())
+        );
+    }
+
+    #[test]
+    fn it_rejects_names_fleet_never_used() {
+        assert_eq!(
+            parse_error("[X-Fleet]\nX-ConditionConflicts=apache@*.service\n"),
+            "unknown option `X-ConditionConflicts` in [X-Fleet] section"
+        );
+        assert_eq!(
+            parse_error("[X-Fleet]\nX-ConditionGlobal=true\n"),
+            "unknown option `X-ConditionGlobal` in [X-Fleet] section"
+        );
+    }
+
+    #[test]
+    fn it_parses_global() {
+        assert!(parse("[X-Fleet]\nGlobal=true\n").global);
+        assert!(parse("[X-Fleet]\nGlobal=TRUE\n").global);
+        assert!(!parse("[X-Fleet]\nGlobal=false\n").global);
+        assert!(!parse("[Service]\nExecStart=/bin/true\n").global);
+        assert!(!parse("[X-Fleet]\nGlobal=true\nGlobal=false\n").global);
+    }
+
+    #[test]
+    fn it_treats_other_global_values_as_false() {
+        for value in ["yes", "on", "1", "t", "sometimes"].iter() {
+            assert!(!parse(&format!("[X-Fleet]\nGlobal={}\n", value)).global);
+        }
+    }
+
+    #[test]
+    fn it_allows_global_units_with_conflicts() {
+        let scheduling = parse("[X-Fleet]\nGlobal=true\nConflicts=apache@*.service\n");
+
+        assert!(scheduling.global);
+        assert_eq!(scheduling.conflicts, vec!["apache@*.service".to_string()]);
+    }
+
+    #[test]
+    fn it_round_trips_through_options() {
+        let scheduling = FleetScheduling::new()
+            .machine_metadata("region", "us-east-1")
+            .conflicts("apache@*.service")
+            .machine_of("database.service");
+
+        let parsed = FleetScheduling::from_options(&scheduling.to_options()).unwrap();
+
+        assert_eq!(parsed, scheduling);
+    }
+
+    #[test]
+    fn it_rejects_unknown_options() {
+        assert_eq!(
+            parse_error("[X-Fleet]\nMachineMetdata=region=us-east-1\n"),
+            "unknown option `MachineMetdata` in [X-Fleet] section"
+        );
+    }
+
+    #[test]
+    fn it_rejects_invalid_metadata() {
+        assert_eq!(
+            parse_error("[X-Fleet]\nMachineMetadata=region\n"),
+            "MachineMetadata value `region` must have the form `key=value`"
+        );
+        assert!(FleetScheduling::new().machine_metadata("region", "").validate().is_err());
+    }
+
+    #[test]
+    fn it_rejects_invalid_conflicts_patterns() {
+        assert_eq!(
+            parse_error("[X-Fleet]\nConflicts=apache@[0-9.service\n"),
+            "Conflicts value `apache@[0-9.service` is invalid: pattern has a malformed character \
+             class"
+        );
+    }
+
+    #[test]
+    fn it_rejects_unresolvable_requirements() {
+        let error = FleetScheduling::new()
+            .machine_of("apache@1.service")
+            .conflicts("apache@*.service")
+            .validate()
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error.message,
+            "unresolvable requirements: MachineOf unit `apache@1.service` matches Conflicts \
+             `apache@*.service`"
+        );
+    }
+
+    #[test]
+    fn it_rejects_incompatible_options() {
+        let error = FleetScheduling::new().machine_id("abc123").global(true).validate();
+
+        assert_eq!(error.err().unwrap().message, "MachineID cannot be used with Global");
+        assert_eq!(
+            parse_error("[X-Fleet]\nConflicts=a.service\nReplaces=b.service\n"),
+            "Conflicts cannot be used with Replaces"
+        );
+    }
+}
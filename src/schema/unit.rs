@@ -1,6 +1,13 @@
+use std::fmt;
+
 use rustc_serialize::json::{Json, ToJson};
 
+use super::hash::unit_hash;
+use super::scheduling::{FleetScheduling, SchedulingError};
+use super::unit_file::render_unit_file;
+
 /// The possible runtime states a unit can be in.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum UnitStates {
     /// The unit has not been loaded onto a machine and is not running.
     Inactive,
@@ -11,42 +18,46 @@ pub enum UnitStates {
 }
 
 impl UnitStates {
-    /// Returns the `UnitStates` variant corresponding to a string representation.
-    ///
-    /// # Panics
-    ///
-    /// Panics if the string representation provided does not match a valid variant.
+    /// Returns the `UnitStates` variant corresponding to a string representation, or `None` if
+    /// the string representation provided does not match a valid variant.
     ///
     /// # Examples
     ///
     /// ```
     /// # use fleet::UnitStates;
-    /// UnitStates::from_str("launched"); // UnitStates::Launched
+    /// UnitStates::from_str("launched"); // Some(UnitStates::Launched)
+    /// UnitStates::from_str("exploded"); // None
     /// ```
-    pub fn from_str(s: &str) -> UnitStates {
+    pub fn from_str(s: &str) -> Option<UnitStates> {
         match s {
-            "inactive" => UnitStates::Inactive,
-            "loaded" => UnitStates::Loaded,
-            "launched" => UnitStates::Launched,
-            variant => panic!("not a valid UnitStates variant: {}", variant),
+            "inactive" => Some(UnitStates::Inactive),
+            "loaded" => Some(UnitStates::Loaded),
+            "launched" => Some(UnitStates::Launched),
+            _ => None,
         }
     }
 }
 
-impl ToJson for UnitStates {
-    fn to_json(&self) -> Json {
+impl fmt::Display for UnitStates {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let value = match *self {
             UnitStates::Inactive => "inactive",
             UnitStates::Loaded => "loaded",
             UnitStates::Launched => "launched",
         };
 
-        Json::String(value.to_string())
+        write!(f, "{}", value)
+    }
+}
+
+impl ToJson for UnitStates {
+    fn to_json(&self) -> Json {
+        Json::String(self.to_string())
     }
 }
 
 /// A single line from a unit file. Unit files consist of key/value pairs divided into sections.
-#[derive(RustcEncodable)]
+#[derive(Clone, Debug, PartialEq, RustcEncodable)]
 pub struct UnitOption {
     /// The key.
     pub name: String,
@@ -57,6 +68,7 @@ pub struct UnitOption {
 }
 
 /// A single fleet unit, which is a systemd unit with optional fleet-specific data.
+#[derive(Clone, Debug)]
 pub struct Unit {
     /// The unit's state.
     pub current_state: UnitStates,
@@ -71,6 +83,39 @@ pub struct Unit {
     pub options: Vec<UnitOption>,
 }
 
+impl Unit {
+    /// Returns the hash fleet assigns to the unit's contents. See `unit_hash` for details.
+    pub fn hash(&self) -> String {
+        unit_hash(&self.options)
+    }
+
+    /// Returns the unit's scheduling requirements from the `[X-Fleet]` section of its options.
+    ///
+    /// # Failures
+    ///
+    /// Returns a `SchedulingError` if the section is invalid. See `FleetScheduling::from_options`
+    /// for details.
+    pub fn scheduling(&self) -> Result<FleetScheduling, SchedulingError> {
+        FleetScheduling::from_options(&self.options)
+    }
+
+    /// Returns the unit's options rendered as the text of a systemd unit file, like
+    /// `fleetctl cat`. See `render_unit_file` for details of the format.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use fleet::Client;
+    /// # let client = Client::new("http://localhost:2999").ok().unwrap();
+    /// let unit = client.get_unit("test.service").ok().unwrap();
+    ///
+    /// print!("{}", unit.to_unit_file());
+    /// ```
+    pub fn to_unit_file(&self) -> String {
+        render_unit_file(&self.options)
+    }
+}
+
 /// A single page from a paginated collection of units.
 pub struct UnitPage {
     /// The units in this page.
@@ -80,6 +125,7 @@ pub struct UnitPage {
 }
 
 /// The current state of a unit.
+#[derive(Clone, Debug)]
 pub struct UnitState {
     /// The unit's name.
     pub name: String,
@@ -123,6 +169,65 @@ mod unit_tests {
             options: vec![unit_option],
         };
     }
+
+    #[test]
+    fn it_can_be_rendered_as_a_unit_file() {
+        let unit_option = UnitOption {
+            name: "Description".to_string(),
+            section: "Unit".to_string(),
+            value: "Example unit".to_string(),
+        };
+
+        let unit = Unit {
+            current_state: UnitStates::Inactive,
+            desired_state: UnitStates::Launched,
+            machine_id: Some("abc123".to_string()),
+            name: "example.service".to_string(),
+            options: vec![unit_option],
+        };
+
+        assert_eq!(unit.to_unit_file(), "[Unit]\nDescription=Example unit\n");
+    }
+
+    #[test]
+    fn it_exposes_its_scheduling_requirements() {
+        let unit_option = UnitOption {
+            name: "Conflicts".to_string(),
+            section: "X-Fleet".to_string(),
+            value: "apache@*.service".to_string(),
+        };
+
+        let unit = Unit {
+            current_state: UnitStates::Inactive,
+            desired_state: UnitStates::Launched,
+            machine_id: None,
+            name: "apache@1.service".to_string(),
+            options: vec![unit_option],
+        };
+
+        let scheduling = unit.scheduling().unwrap();
+
+        assert_eq!(scheduling.conflicts, vec!["apache@*.service".to_string()]);
+    }
+
+    #[test]
+    fn it_can_be_hashed() {
+        let unit_option = UnitOption {
+            name: "ExecStart".to_string(),
+            section: "Service".to_string(),
+            value: "/bin/sleep 100".to_string(),
+        };
+
+        let unit = Unit {
+            current_state: UnitStates::Inactive,
+            desired_state: UnitStates::Launched,
+            machine_id: None,
+            name: "example.service".to_string(),
+            options: vec![unit_option],
+        };
+
+        assert_eq!(unit.hash(), "1c6fb6f3684bafb0c173d8b8b957ceff031180c1");
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,527 @@
+use std::error::Error;
+use std::fmt;
+
+use super::unit::UnitOption;
+
+/// An error encountered while parsing a unit file.
+#[derive(Debug)]
+pub struct UnitFileError {
+    /// The line of the unit file on which the error occurred, starting at 1.
+    pub line: usize,
+    /// A message describing the error.
+    pub message: String,
+}
+
+impl fmt::Display for UnitFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for UnitFileError {
+    fn description(&self) -> &str {
+        &self.message[..]
+    }
+}
+
+/// Parses the contents of a systemd unit file into the unit options fleet expects.
+///
+/// Parsing follows the same rules fleetctl uses when it submits a unit file, so the options
+/// returned are exactly what fleetctl would send for the same file:
+///
+/// * Blank lines and lines starting with `#` or `;` are ignored.
+/// * Keys may be repeated. Every assignment becomes its own option, in the order it appears.
+/// * An empty assignment such as `ExecStartPre=` becomes an option with an empty value in its
+///   original position, so systemd still resets the list when the unit runs.
+/// * A value ending in a backslash continues onto the next line. The backslash and line break are
+///   kept in the value, as systemd expects to see them in the unit file.
+/// * Quotes are kept verbatim. Only the leading and trailing whitespace of a value is removed.
+///
+/// # Failures
+///
+/// Returns a `UnitFileError` if a section header is not closed, if a line is not an assignment,
+/// or if an assignment appears before the first section header.
+///
+/// # Examples
+///
+/// ```
+/// use fleet::parse_unit_file;
+///
+/// let options = parse_unit_file("[Service]\nExecStart=/usr/bin/sleep 3000\n").unwrap();
+///
+/// assert_eq!(options[0].section, "Service");
+/// assert_eq!(options[0].name, "ExecStart");
+/// assert_eq!(options[0].value, "/usr/bin/sleep 3000");
+/// ```
+pub fn parse_unit_file(contents: &str) -> Result<Vec<UnitOption>, UnitFileError> {
+    let lines: Vec<&str> = contents.split('\n').collect();
+    let mut options = vec![];
+    let mut section: Option<String> = None;
+    let mut index = 0;
+
+    while index < lines.len() {
+        let line = lines[index];
+        let line_number = index + 1;
+        let trimmed = line.trim();
+
+        index += 1;
+
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with(';') {
+            continue;
+        }
+
+        if trimmed.starts_with('[') {
+            match trimmed.find(']') {
+                Some(end) => section = Some(trimmed[1..end].to_string()),
+                None => return Err(UnitFileError {
+                    line: line_number,
+                    message: "section header is missing a closing `]`".to_string(),
+                }),
+            }
+
+            continue;
+        }
+
+        let equals = match line.find('=') {
+            Some(equals) => equals,
+            None => return Err(UnitFileError {
+                line: line_number,
+                message: format!("expected an assignment, found {:?}", trimmed),
+            }),
+        };
+
+        let name = line[..equals].trim();
+
+        if name.is_empty() {
+            return Err(UnitFileError {
+                line: line_number,
+                message: "assignment is missing a key".to_string(),
+            });
+        }
+
+        let section = match section {
+            Some(ref section) => section.clone(),
+            None => return Err(UnitFileError {
+                line: line_number,
+                message: format!("assignment to {} appears before any section", name),
+            }),
+        };
+
+        let mut value = String::new();
+        let mut segment = line[equals + 1..].trim_left_matches(|c| c == ' ' || c == '\t');
+
+        loop {
+            if segment.trim().is_empty() {
+                break;
+            }
+
+            value.push_str(segment);
+
+            if !segment.ends_with('\\') || index >= lines.len() {
+                break;
+            }
+
+            value.push('\n');
+            segment = lines[index];
+            index += 1;
+        }
+
+        // A continuation followed by a blank line leaves a trailing line break, which belongs to
+        // the value.
+        let value = if value.ends_with('\n') {
+            format!("{}\n", value.trim())
+        } else {
+            value.trim().to_string()
+        };
+
+        options.push(UnitOption {
+            name: name.to_string(),
+            section: section,
+            value: value,
+        });
+    }
+
+    Ok(options)
+}
+
+/// Renders unit options as the text of a systemd unit file, the way `fleetctl cat` shows them.
+///
+/// Options are grouped by section, with sections in the order they first appear and options in
+/// their original order within each section. This is the same layout fleet uses when it writes a
+/// unit file to disk. A line break in a value that is not already escaped with a backslash is
+/// escaped so that it becomes a continuation line.
+///
+/// Options returned by `parse_unit_file` render to text that parses back to the same options, so
+/// a unit can be saved and later re-submitted unchanged. A value with an unescaped line break
+/// parses back with the backslash in front of it, which renders to the same text again.
+///
+/// # Examples
+///
+/// ```
+/// use fleet::{render_unit_file, UnitOption};
+///
+/// let options = vec![
+///     UnitOption {
+///         name: "ExecStart".to_string(),
+///         section: "Service".to_string(),
+///         value: "/usr/bin/sleep 3000".to_string(),
+///     },
+/// ];
+///
+/// assert_eq!(render_unit_file(&options), "[Service]\nExecStart=/usr/bin/sleep 3000\n");
+/// ```
+pub fn render_unit_file(options: &[UnitOption]) -> String {
+    write_unit_file(options, true)
+}
+
+/// Serializes unit options the way fleet does before hashing them: in the same layout as
+/// `render_unit_file`, but with every value written verbatim.
+pub fn serialize_unit_file(options: &[UnitOption]) -> String {
+    write_unit_file(options, false)
+}
+
+fn write_unit_file(options: &[UnitOption], escape_values: bool) -> String {
+    let mut sections: Vec<&str> = vec![];
+
+    for option in options.iter() {
+        if !sections.contains(&&option.section[..]) {
+            sections.push(&option.section[..]);
+        }
+    }
+
+    let rendered: Vec<String> = sections.iter().map(|section| {
+        let mut text = format!("[{}]\n", section);
+
+        for option in options.iter().filter(|option| option.section == *section) {
+            let value = if escape_values {
+                escape_value(&option.value)
+            } else {
+                option.value.clone()
+            };
+
+            text.push_str(&format!("{}={}\n", option.name, value));
+        }
+
+        text
+    }).collect();
+
+    rendered.join("\n")
+}
+
+fn escape_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    let mut previous = None;
+
+    for c in value.chars() {
+        if c == '\n' && previous != Some('\\') {
+            escaped.push('\\');
+        }
+
+        escaped.push(c);
+        previous = Some(c);
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod unit_file_tests {
+    use super::{parse_unit_file, render_unit_file, serialize_unit_file};
+    use super::super::unit::UnitOption;
+
+    fn option(section: &str, name: &str, value: &str) -> (String, String, String) {
+        (section.to_string(), name.to_string(), value.to_string())
+    }
+
+    fn triples(options: &[UnitOption]) -> Vec<(String, String, String)> {
+        options.iter().map(|option| {
+            (option.section.clone(), option.name.clone(), option.value.clone())
+        }).collect()
+    }
+
+    const HELLO: &'static str = "[Unit]
+Description=Hello World
+
+[Service]
+ExecStart=/bin/bash -c \"while true; do echo \\\"Hello, world\\\"; sleep 1; done\"
+";
+
+    const APACHE: &'static str = "[Unit]
+Description=My Apache Frontend
+After=docker.service
+Requires=docker.service
+
+[Service]
+TimeoutStartSec=0
+ExecStartPre=-/usr/bin/docker kill apache1
+ExecStartPre=-/usr/bin/docker rm apache1
+ExecStartPre=/usr/bin/docker pull coreos/apache
+ExecStart=/usr/bin/docker run -rm --name apache1 -p 80:80 coreos/apache /usr/sbin/apache2ctl -D FOREGROUND
+ExecStop=/usr/bin/docker stop apache1
+
+[X-Fleet]
+Conflicts=apache@*.service
+";
+
+    const DISCOVERY: &'static str = "[Unit]
+Description=Announce Apache1
+BindsTo=apache.1.service
+After=apache.1.service
+
+[Service]
+ExecStart=/bin/sh -c \"while true; do etcdctl set /services/website/apache1 \\
+  '{ \\\"host\\\": \\\"%H\\\", \\\"port\\\": 80 }' --ttl 60;sleep 45;done\"
+ExecStop=/usr/bin/etcdctl rm /services/website/apache1
+
+[X-Fleet]
+MachineOf=apache.1.service
+";
+
+    #[test]
+    fn it_parses_sections_and_options() {
+        let options = parse_unit_file(APACHE).unwrap();
+
+        assert_eq!(triples(&options), vec![
+            option("Unit", "Description", "My Apache Frontend"),
+            option("Unit", "After", "docker.service"),
+            option("Unit", "Requires", "docker.service"),
+            option("Service", "TimeoutStartSec", "0"),
+            option("Service", "ExecStartPre", "-/usr/bin/docker kill apache1"),
+            option("Service", "ExecStartPre", "-/usr/bin/docker rm apache1"),
+            option("Service", "ExecStartPre", "/usr/bin/docker pull coreos/apache"),
+            option(
+                "Service",
+                "ExecStart",
+                "/usr/bin/docker run -rm --name apache1 -p 80:80 coreos/apache \
+                 /usr/sbin/apache2ctl -D FOREGROUND",
+            ),
+            option("Service", "ExecStop", "/usr/bin/docker stop apache1"),
+            option("X-Fleet", "Conflicts", "apache@*.service"),
+        ]);
+    }
+
+    #[test]
+    fn it_keeps_quotes_verbatim() {
+        let options = parse_unit_file(HELLO).unwrap();
+
+        assert_eq!(
+            options[1].value,
+            "/bin/bash -c \"while true; do echo \\\"Hello, world\\\"; sleep 1; done\""
+        );
+    }
+
+    #[test]
+    fn it_keeps_line_continuations() {
+        let options = parse_unit_file(DISCOVERY).unwrap();
+
+        assert_eq!(
+            options[3].value,
+            "/bin/sh -c \"while true; do etcdctl set /services/website/apache1 \\\n  \
+             '{ \\\"host\\\": \\\"%H\\\", \\\"port\\\": 80 }' --ttl 60;sleep 45;done\""
+        );
+        assert_eq!(options[4].name, "ExecStop");
+    }
+
+    #[test]
+    fn it_round_trips_canonical_unit_files() {
+        for contents in [HELLO, APACHE, DISCOVERY].iter() {
+            let options = parse_unit_file(contents).unwrap();
+            let rendered = render_unit_file(&options);
+
+            assert_eq!(&rendered[..], *contents);
+            assert_eq!(parse_unit_file(&rendered).unwrap(), options);
+        }
+    }
+
+    #[test]
+    fn it_ignores_comments_and_whitespace() {
+        let contents = "# A comment\n; Another comment\n\n[Service]\n  # Indented comment\n  \
+                        ExecStart =   /usr/bin/sleep 3000   \n";
+
+        let options = parse_unit_file(contents).unwrap();
+
+        assert_eq!(triples(&options), vec![
+            option("Service", "ExecStart", "/usr/bin/sleep 3000"),
+        ]);
+    }
+
+    #[test]
+    fn it_does_not_treat_a_hash_inside_a_value_as_a_comment() {
+        let options = parse_unit_file("[Service]\nExecStart=/bin/echo \"# not a comment\"").unwrap();
+
+        assert_eq!(options[0].value, "/bin/echo \"# not a comment\"");
+    }
+
+    #[test]
+    fn it_keeps_empty_assignments_in_place() {
+        let contents = "[Service]\nExecStartPre=/bin/true\nExecStartPre=\nExecStartPre=/bin/false\n";
+
+        let options = parse_unit_file(contents).unwrap();
+
+        assert_eq!(triples(&options), vec![
+            option("Service", "ExecStartPre", "/bin/true"),
+            option("Service", "ExecStartPre", ""),
+            option("Service", "ExecStartPre", "/bin/false"),
+        ]);
+    }
+
+    #[test]
+    fn it_keeps_file_order_across_repeated_sections() {
+        let contents = "[Unit]\nDescription=a\n[Service]\nExecStart=/bin/true\n[Unit]\nAfter=b\n";
+
+        let options = parse_unit_file(contents).unwrap();
+
+        assert_eq!(triples(&options), vec![
+            option("Unit", "Description", "a"),
+            option("Service", "ExecStart", "/bin/true"),
+            option("Unit", "After", "b"),
+        ]);
+    }
+
+    #[test]
+    fn it_ends_a_continuation_at_a_blank_line() {
+        let options = parse_unit_file("[Service]\nExecStart=/bin/echo \\\n\nUser=core\n").unwrap();
+
+        assert_eq!(options[0].value, "/bin/echo \\\n");
+        assert_eq!(options[1].name, "User");
+    }
+
+    #[test]
+    fn it_ends_a_continuation_at_the_end_of_the_file() {
+        let options = parse_unit_file("[Service]\nExecStart=/bin/echo \\").unwrap();
+
+        assert_eq!(options[0].value, "/bin/echo \\");
+    }
+
+    #[test]
+    fn it_rejects_unclosed_section_headers() {
+        let error = parse_unit_file("[Service\nExecStart=/bin/true\n").err().unwrap();
+
+        assert_eq!(format!("{}", error), "line 1: section header is missing a closing `]`");
+    }
+
+    #[test]
+    fn it_rejects_lines_that_are_not_assignments() {
+        let error = parse_unit_file("[Service]\nExecStart\n").err().unwrap();
+
+        assert_eq!(format!("{}", error), "line 2: expected an assignment, found \"ExecStart\"");
+    }
+
+    #[test]
+    fn it_rejects_assignments_outside_of_a_section() {
+        let error = parse_unit_file("ExecStart=/bin/true\n").err().unwrap();
+
+        assert_eq!(
+            format!("{}", error),
+            "line 1: assignment to ExecStart appears before any section"
+        );
+    }
+
+    #[test]
+    fn it_renders_nothing_for_no_options() {
+        assert_eq!(render_unit_file(&[]), "");
+    }
+
+    #[test]
+    fn it_groups_options_by_section_in_order_of_appearance() {
+        let options = vec![
+            UnitOption {
+                name: "Description".to_string(),
+                section: "Unit".to_string(),
+                value: "a".to_string(),
+            },
+            UnitOption {
+                name: "ExecStart".to_string(),
+                section: "Service".to_string(),
+                value: "/bin/true".to_string(),
+            },
+            UnitOption {
+                name: "After".to_string(),
+                section: "Unit".to_string(),
+                value: "b".to_string(),
+            },
+        ];
+
+        assert_eq!(
+            render_unit_file(&options),
+            "[Unit]\nDescription=a\nAfter=b\n\n[Service]\nExecStart=/bin/true\n"
+        );
+    }
+
+    #[test]
+    fn it_round_trips_continuations_and_empty_values() {
+        let options = vec![
+            UnitOption {
+                name: "ExecStart".to_string(),
+                section: "Service".to_string(),
+                value: "/bin/echo one \\\n  two".to_string(),
+            },
+            UnitOption {
+                name: "ExecStartPre".to_string(),
+                section: "Service".to_string(),
+                value: "".to_string(),
+            },
+            UnitOption {
+                name: "Environment".to_string(),
+                section: "Service".to_string(),
+                value: "GREETING=\"hello world\"".to_string(),
+            },
+        ];
+
+        let rendered = render_unit_file(&options);
+
+        assert_eq!(
+            rendered,
+            "[Service]\nExecStart=/bin/echo one \\\n  two\nExecStartPre=\n\
+             Environment=GREETING=\"hello world\"\n"
+        );
+        assert_eq!(parse_unit_file(&rendered).unwrap(), options);
+    }
+
+    #[test]
+    fn it_escapes_bare_line_breaks_as_continuations() {
+        let options = vec![
+            UnitOption {
+                name: "ExecStart".to_string(),
+                section: "Service".to_string(),
+                value: "/bin/echo one\n  two\n  three".to_string(),
+            },
+            UnitOption {
+                name: "User".to_string(),
+                section: "Service".to_string(),
+                value: "core".to_string(),
+            },
+        ];
+
+        let rendered = render_unit_file(&options);
+
+        assert_eq!(rendered, "[Service]\nExecStart=/bin/echo one\\\n  two\\\n  three\nUser=core\n");
+
+        let parsed = parse_unit_file(&rendered).unwrap();
+
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].value, "/bin/echo one\\\n  two\\\n  three");
+        assert_eq!(parsed[1].value, "core");
+        assert_eq!(render_unit_file(&parsed), rendered);
+    }
+
+    #[test]
+    fn it_does_not_escape_existing_continuations_twice() {
+        let options = parse_unit_file(DISCOVERY).unwrap();
+
+        assert_eq!(render_unit_file(&options), DISCOVERY);
+    }
+
+    #[test]
+    fn it_serializes_values_verbatim() {
+        let options = vec![
+            UnitOption {
+                name: "ExecStart".to_string(),
+                section: "Service".to_string(),
+                value: "/bin/echo one\n  two".to_string(),
+            },
+        ];
+
+        assert_eq!(serialize_unit_file(&options), "[Service]\nExecStart=/bin/echo one\n  two\n");
+    }
+}
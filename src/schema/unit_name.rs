@@ -0,0 +1,307 @@
+use std::error::Error;
+use std::fmt;
+
+/// An error describing why a string is not a valid unit name.
+#[derive(Debug)]
+pub struct UnitNameError {
+    /// The name that was rejected.
+    pub name: String,
+    /// A message describing the problem.
+    pub message: String,
+}
+
+impl UnitNameError {
+    fn new(name: &str, message: &str) -> UnitNameError {
+        UnitNameError {
+            name: name.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for UnitNameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid unit name {:?}: {}", self.name, self.message)
+    }
+}
+
+impl Error for UnitNameError {
+    fn description(&self) -> &str {
+        &self.message[..]
+    }
+}
+
+/// The unit types fleet can schedule.
+const SUPPORTED_TYPES: [&'static str; 7] = [
+    "service",
+    "socket",
+    "timer",
+    "path",
+    "device",
+    "mount",
+    "automount",
+];
+
+/// The longest unit name systemd accepts.
+const MAX_LENGTH: usize = 255;
+
+/// A unit name split into its parts.
+///
+/// systemd unit names have the form `prefix.type`, e.g. `web.service`. Template units have an
+/// `@` at the end of their prefix, e.g. `web@.service`, and are never run themselves. Instead,
+/// fleet runs instances of them, whose names add an instance string after the `@`, e.g.
+/// `web@1.service`.
+///
+/// Names are held to the same rules systemd and fleet apply: at most 255 characters, made up of
+/// ASCII letters, digits and `:-_.\@`, with a prefix, at most one `@`, and one of the types
+/// fleet supports: `service`, `socket`, `timer`, `path`, `device`, `mount` and `automount`.
+/// `Client` checks every unit name this way before sending it to fleet.
+///
+/// # Examples
+///
+/// ```
+/// use fleet::UnitName;
+///
+/// let template = UnitName::parse("web@.service").unwrap();
+///
+/// assert!(template.is_template());
+///
+/// let instance = template.instance_name("1").unwrap();
+///
+/// assert_eq!(instance.to_string(), "web@1.service");
+/// assert_eq!(instance.instance(), Some("1"));
+/// assert_eq!(instance.template().unwrap(), template);
+/// ```
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct UnitName {
+    prefix: String,
+    instance: Option<String>,
+    unit_type: String,
+}
+
+impl UnitName {
+    /// Checks that `name` is a valid unit name and splits it into its parts.
+    ///
+    /// # Failures
+    ///
+    /// Returns a `UnitNameError` describing the first rule `name` breaks.
+    pub fn parse(name: &str) -> Result<UnitName, UnitNameError> {
+        if name.is_empty() {
+            return Err(UnitNameError::new(name, "is empty"));
+        }
+
+        if name.len() > MAX_LENGTH {
+            return Err(UnitNameError::new(
+                name,
+                &format!("is longer than {} characters", MAX_LENGTH)
+            ));
+        }
+
+        if let Some(invalid) = name.chars().find(|c| !is_valid_char(*c)) {
+            return Err(UnitNameError::new(
+                name,
+                &format!("contains the invalid character {:?}", invalid)
+            ));
+        }
+
+        let (stem, unit_type) = match name.rfind('.') {
+            Some(index) => (&name[..index], &name[index + 1..]),
+            None => return Err(UnitNameError::new(name, "has no type suffix, such as .service")),
+        };
+
+        if unit_type.is_empty() {
+            return Err(UnitNameError::new(name, "has an empty type suffix"));
+        }
+
+        let (prefix, instance) = match stem.find('@') {
+            Some(index) => (&stem[..index], Some(&stem[index + 1..])),
+            None => (stem, None),
+        };
+
+        if prefix.is_empty() {
+            return Err(UnitNameError::new(name, "has an empty prefix"));
+        }
+
+        if instance.map_or(false, |instance| instance.contains('@')) {
+            return Err(UnitNameError::new(name, "has more than one @"));
+        }
+
+        if !SUPPORTED_TYPES.contains(&unit_type) {
+            return Err(UnitNameError::new(
+                name,
+                &format!("has the type .{}, which fleet does not support", unit_type)
+            ));
+        }
+
+        Ok(UnitName {
+            prefix: prefix.to_string(),
+            instance: instance.map(|instance| instance.to_string()),
+            unit_type: unit_type.to_string(),
+        })
+    }
+
+    /// Returns the part of the name before the `@`, or before the type if there is no `@`.
+    pub fn prefix(&self) -> &str {
+        &self.prefix[..]
+    }
+
+    /// Returns the instance string of an instance, or `None` for a template or a unit that is
+    /// neither.
+    pub fn instance(&self) -> Option<&str> {
+        match self.instance {
+            Some(ref instance) if !instance.is_empty() => Some(&instance[..]),
+            _ => None,
+        }
+    }
+
+    /// Returns the unit type, e.g. `service`, without the leading `.`.
+    pub fn unit_type(&self) -> &str {
+        &self.unit_type[..]
+    }
+
+    /// Returns whether this is the name of a template, e.g. `web@.service`.
+    pub fn is_template(&self) -> bool {
+        self.instance.as_ref().map_or(false, |instance| instance.is_empty())
+    }
+
+    /// Returns whether this is the name of an instance of a template, e.g. `web@1.service`.
+    pub fn is_instance(&self) -> bool {
+        self.instance().is_some()
+    }
+
+    /// Returns whether this is the name of an instance of `template`.
+    pub fn is_instance_of(&self, template: &UnitName) -> bool {
+        self.is_instance() && self.template().as_ref() == Some(template)
+    }
+
+    /// Returns the name of the template this is an instance of, or `None` if it is not an
+    /// instance.
+    pub fn template(&self) -> Option<UnitName> {
+        if !self.is_instance() {
+            return None;
+        }
+
+        Some(UnitName {
+            prefix: self.prefix.clone(),
+            instance: Some(String::new()),
+            unit_type: self.unit_type.clone(),
+        })
+    }
+
+    /// Returns the name of the instance of this template, or of the template this is an instance
+    /// of, with the instance string `instance`.
+    ///
+    /// # Failures
+    ///
+    /// Returns a `UnitNameError` if this is neither a template nor an instance, if `instance`
+    /// is empty or contains a `@`, or if the resulting name is invalid.
+    pub fn instance_name(&self, instance: &str) -> Result<UnitName, UnitNameError> {
+        if self.instance.is_none() {
+            return Err(UnitNameError::new(&self.to_string(), "is not a template"));
+        }
+
+        let name = format!("{}@{}.{}", self.prefix, instance, self.unit_type);
+
+        if instance.is_empty() || instance.contains('@') {
+            return Err(UnitNameError::new(&name, "has an invalid instance string"));
+        }
+
+        UnitName::parse(&name)
+    }
+}
+
+impl fmt::Display for UnitName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.instance {
+            Some(ref instance) => {
+                write!(f, "{}@{}.{}", self.prefix, instance, self.unit_type)
+            },
+            None => write!(f, "{}.{}", self.prefix, self.unit_type),
+        }
+    }
+}
+
+fn is_valid_char(c: char) -> bool {
+    c.is_ascii() && (c.is_alphanumeric() || ":-_.\\@".contains(c))
+}
+
+#[cfg(test)]
+mod unit_name_tests {
+    use super::UnitName;
+
+    #[test]
+    fn it_parses_plain_names() {
+        let name = UnitName::parse("web.service").unwrap();
+
+        assert_eq!(name.prefix(), "web");
+        assert_eq!(name.instance(), None);
+        assert_eq!(name.unit_type(), "service");
+        assert!(!name.is_template());
+        assert!(!name.is_instance());
+        assert!(name.template().is_none());
+        assert!(name.instance_name("1").is_err());
+        assert_eq!(name.to_string(), "web.service");
+    }
+
+    #[test]
+    fn it_parses_templates_and_instances() {
+        let template = UnitName::parse("web@.service").unwrap();
+        let instance = UnitName::parse("web@8080.service").unwrap();
+        let other = UnitName::parse("api@8080.service").unwrap();
+
+        assert!(template.is_template());
+        assert_eq!(template.instance(), None);
+        assert!(instance.is_instance());
+        assert_eq!(instance.instance(), Some("8080"));
+        assert!(instance.is_instance_of(&template));
+        assert!(!other.is_instance_of(&template));
+        assert!(!template.is_instance_of(&template));
+        assert_eq!(instance.instance_name("8081").unwrap().to_string(), "web@8081.service");
+    }
+
+    #[test]
+    fn it_rejects_malformed_names() {
+        assert_eq!(
+            UnitName::parse("web").err().unwrap().to_string(),
+            r#"Invalid unit name "web": has no type suffix, such as .service"#
+        );
+        assert!(UnitName::parse("web.").is_err());
+        assert!(UnitName::parse(".service").is_err());
+        assert!(UnitName::parse("@1.service").is_err());
+        assert!(UnitName::parse("web@1@2.service").is_err());
+        assert!(UnitName::parse("web.target").is_err());
+    }
+
+    #[test]
+    fn it_applies_the_systemd_rules() {
+        assert!(UnitName::parse("dev-disk-by\\x2duuid:1_a.mount").is_ok());
+        assert_eq!(
+            UnitName::parse("").err().unwrap().to_string(),
+            r#"Invalid unit name "": is empty"#
+        );
+        assert_eq!(
+            UnitName::parse("my web.service").err().unwrap().message,
+            "contains the invalid character ' '"
+        );
+        assert!(UnitName::parse("../web.service").is_err());
+        assert!(UnitName::parse("wéb.service").is_err());
+        assert_eq!(
+            UnitName::parse("web.conf").err().unwrap().message,
+            "has the type .conf, which fleet does not support"
+        );
+
+        let longest = format!("{}.service", "a".repeat(247));
+
+        assert!(UnitName::parse(&longest).is_ok());
+        assert_eq!(
+            UnitName::parse(&format!("a{}", longest)).err().unwrap().message,
+            "is longer than 255 characters"
+        );
+
+        let template = UnitName::parse("web@.service").unwrap();
+
+        assert!(template.instance_name("").is_err());
+        assert!(template.instance_name("a@b").is_err());
+        assert_eq!(template.instance_name("a.b").unwrap().instance(), Some("a.b"));
+    }
+}
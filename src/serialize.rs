@@ -1,8 +1,19 @@
 use std::collections::{BTreeMap, HashMap};
 
-use rustc_serialize::json::Json;
+use rustc_serialize::json::{Json, ToJson};
 
-use schema::{Machine, Unit, UnitOption, UnitState, UnitStates};
+use error::{DecodeError, FleetError, SnapshotError};
+use schema::{
+    Machine,
+    MachinePage,
+    Unit,
+    UnitOption,
+    UnitPage,
+    UnitState,
+    UnitStatePage,
+    UnitStates
+};
+use snapshot::{self, Snapshot};
 
 #[derive(RustcEncodable)]
 #[allow(non_snake_case)]
@@ -17,79 +28,443 @@ pub struct ModifyUnit {
     pub desiredState: Json,
 }
 
-pub fn get_metadata_hashmap(json_obj: &BTreeMap<String, Json>) -> HashMap<String, String> {
+fn decode_error(resource: &'static str, field: &str, message: &str) -> FleetError {
+    FleetError::Decode(DecodeError {
+        resource: resource,
+        field: field.to_string(),
+        message: message.to_string(),
+    })
+}
+
+fn get_object<'a>(
+    json: &'a Json,
+    resource: &'static str,
+) -> Result<&'a BTreeMap<String, Json>, FleetError> {
+    match json.as_object() {
+        Some(json_obj) => Ok(json_obj),
+        None => Err(decode_error(resource, "", "is not a JSON object")),
+    }
+}
+
+fn get_array<'a>(
+    json: &'a Json,
+    resource: &'static str,
+    key: &str,
+) -> Result<&'a [Json], FleetError> {
+    match json.find(key) {
+        Some(value) => match value.as_array() {
+            Some(array) => Ok(&array[..]),
+            None => Err(decode_error(resource, key, "is not an array")),
+        },
+        None => Ok(&[]),
+    }
+}
+
+pub fn get_metadata_hashmap(
+    json_obj: &BTreeMap<String, Json>,
+) -> Result<HashMap<String, String>, FleetError> {
     let mut metadata = HashMap::new();
 
     match json_obj.get("metadata") {
         Some(metadata_json) => {
-            let metadata_json_obj = metadata_json.as_object().unwrap();
+            let metadata_json_obj = match metadata_json.as_object() {
+                Some(metadata_json_obj) => metadata_json_obj,
+                None => return Err(decode_error("machine", "metadata", "is not an object")),
+            };
 
             for (key, value) in metadata_json_obj.iter() {
-                metadata.insert(key.clone(), value.as_string().unwrap().to_string());
+                match value.as_string() {
+                    Some(value) => metadata.insert(key.clone(), value.to_string()),
+                    None => return Err(decode_error(
+                        "machine",
+                        &format!("metadata.{}", key),
+                        "is not a string",
+                    )),
+                };
             }
 
-            metadata
+            Ok(metadata)
         },
-        None => metadata,
+        None => Ok(metadata),
     }
 }
 
-pub fn get_string_value<'a>(json_obj: &'a BTreeMap<String, Json>, key: &str) -> Option<&'a str> {
+fn get_required_string(
+    json_obj: &BTreeMap<String, Json>,
+    resource: &'static str,
+    key: &str,
+) -> Result<String, FleetError> {
     match json_obj.get(key) {
-        Some(value) => value.as_string(),
-        None => None,
+        Some(value) => match value.as_string() {
+            Some(value) => Ok(value.to_string()),
+            None => Err(decode_error(resource, key, "is not a string")),
+        },
+        None => Err(decode_error(resource, key, "is missing")),
+    }
+}
+
+fn get_optional_string(
+    json_obj: &BTreeMap<String, Json>,
+    resource: &'static str,
+    key: &str,
+) -> Result<Option<String>, FleetError> {
+    match json_obj.get(key) {
+        Some(&Json::Null) | None => Ok(None),
+        Some(value) => match value.as_string() {
+            Some(value) => Ok(Some(value.to_string())),
+            None => Err(decode_error(resource, key, "is not a string")),
+        },
     }
 }
 
-pub fn machine_from_json(json: &Json) -> Machine {
-    let machine_obj = json.as_object().unwrap();
+fn get_unit_states(
+    json_obj: &BTreeMap<String, Json>,
+    resource: &'static str,
+    key: &str,
+) -> Result<UnitStates, FleetError> {
+    let value = try!(get_required_string(json_obj, resource, key));
 
-    Machine {
-        id: get_string_value(machine_obj, "id").unwrap().to_string(),
-        metadata: get_metadata_hashmap(machine_obj),
-        primary_ip: get_string_value(machine_obj, "primaryIP").unwrap().to_string(),
+    match UnitStates::from_str(&value) {
+        Some(unit_states) => Ok(unit_states),
+        None => Err(decode_error(
+            resource,
+            key,
+            &format!("is not a valid unit state: {:?}", value),
+        )),
     }
 }
 
-pub fn unit_from_json(json: &Json) -> Unit {
-    let unit_obj = json.as_object().unwrap();
+fn get_version(
+    json_obj: &BTreeMap<String, Json>,
+    resource: &'static str,
+) -> Result<u64, FleetError> {
+    match json_obj.get("version") {
+        Some(value) => match value.as_u64() {
+            Some(version) => Ok(version),
+            None => Err(decode_error(resource, "version", "is not a positive integer")),
+        },
+        None => Err(decode_error(resource, "version", "is missing")),
+    }
+}
 
-    Unit {
-        current_state: UnitStates::from_str(get_string_value(unit_obj, "currentState").unwrap()),
-        desired_state: UnitStates::from_str(get_string_value(unit_obj, "desiredState").unwrap()),
-        machine_id: match get_string_value(unit_obj, "machineID") {
-            Some(value) => Some(value.to_string()),
+pub fn get_next_page_token(json: &Json) -> Option<String> {
+    match json.find("nextPageToken") {
+        Some(next_page_token_json) => match next_page_token_json.as_string() {
+            Some(next_page_token) => Some(next_page_token.to_string()),
             None => None,
         },
-        name: get_string_value(unit_obj, "name").unwrap().to_string(),
-        options: unit_obj.get("options").unwrap().as_array().unwrap().iter().map(|opt_json| {
-            unit_option_from_json(opt_json)
-        }).collect(),
+        None => None,
     }
 }
 
-pub fn unit_option_from_json(json: &Json) -> UnitOption {
-    let unit_obj = json.as_object().unwrap();
+pub fn machine_from_json(json: &Json) -> Result<Machine, FleetError> {
+    let machine_obj = try!(get_object(json, "machine"));
+
+    Ok(Machine {
+        id: try!(get_required_string(machine_obj, "machine", "id")),
+        metadata: try!(get_metadata_hashmap(machine_obj)),
+        primary_ip: try!(get_required_string(machine_obj, "machine", "primaryIP")),
+    })
+}
+
+pub fn machine_page_from_json(json: &Json) -> Result<MachinePage, FleetError> {
+    try!(get_object(json, "machine page"));
 
-    UnitOption {
-        name: get_string_value(unit_obj, "name").unwrap().to_string(),
-        section: get_string_value(unit_obj, "section").unwrap().to_string(),
-        value: get_string_value(unit_obj, "value").unwrap().to_string(),
+    let mut machines = vec![];
+
+    for machine_json in try!(get_array(json, "machine page", "machines")).iter() {
+        machines.push(try!(machine_from_json(machine_json)));
     }
+
+    Ok(MachinePage {
+        machines: machines,
+        next_page_token: get_next_page_token(json),
+    })
 }
 
-pub fn unit_state_from_json(json: &Json) -> UnitState {
-    let unit_obj = json.as_object().unwrap();
+pub fn unit_from_json(json: &Json) -> Result<Unit, FleetError> {
+    let unit_obj = try!(get_object(json, "unit"));
 
-    UnitState {
-        name: get_string_value(unit_obj, "name").unwrap().to_string(),
-        hash: get_string_value(unit_obj, "hash").unwrap().to_string(),
-        machine_id: match get_string_value(unit_obj, "machineID") {
-            Some(value) => Some(value.to_string()),
-            None => None,
-        },
-        systemd_load_state: get_string_value(unit_obj, "systemdLoadState").unwrap().to_string(),
-        systemd_active_state: get_string_value(unit_obj, "systemdActiveState").unwrap().to_string(),
-        systemd_sub_state: get_string_value(unit_obj, "systemdSubState").unwrap().to_string(),
+    let mut options = vec![];
+
+    for option_json in try!(get_array(json, "unit", "options")).iter() {
+        options.push(try!(unit_option_from_json(option_json)));
+    }
+
+    Ok(Unit {
+        current_state: try!(get_unit_states(unit_obj, "unit", "currentState")),
+        desired_state: try!(get_unit_states(unit_obj, "unit", "desiredState")),
+        machine_id: try!(get_optional_string(unit_obj, "unit", "machineID")),
+        name: try!(get_required_string(unit_obj, "unit", "name")),
+        options: options,
+    })
+}
+
+pub fn unit_page_from_json(json: &Json) -> Result<UnitPage, FleetError> {
+    try!(get_object(json, "unit page"));
+
+    let mut units = vec![];
+
+    for unit_json in try!(get_array(json, "unit page", "units")).iter() {
+        units.push(try!(unit_from_json(unit_json)));
+    }
+
+    Ok(UnitPage {
+        units: units,
+        next_page_token: get_next_page_token(json),
+    })
+}
+
+pub fn unit_option_from_json(json: &Json) -> Result<UnitOption, FleetError> {
+    let unit_obj = try!(get_object(json, "unit option"));
+
+    Ok(UnitOption {
+        name: try!(get_required_string(unit_obj, "unit option", "name")),
+        section: try!(get_required_string(unit_obj, "unit option", "section")),
+        value: try!(get_required_string(unit_obj, "unit option", "value")),
+    })
+}
+
+pub fn unit_state_from_json(json: &Json) -> Result<UnitState, FleetError> {
+    let unit_obj = try!(get_object(json, "unit state"));
+
+    Ok(UnitState {
+        name: try!(get_required_string(unit_obj, "unit state", "name")),
+        hash: try!(get_required_string(unit_obj, "unit state", "hash")),
+        machine_id: try!(get_optional_string(unit_obj, "unit state", "machineID")),
+        systemd_load_state: try!(get_required_string(
+            unit_obj,
+            "unit state",
+            "systemdLoadState",
+        )),
+        systemd_active_state: try!(get_required_string(
+            unit_obj,
+            "unit state",
+            "systemdActiveState",
+        )),
+        systemd_sub_state: try!(get_required_string(
+            unit_obj,
+            "unit state",
+            "systemdSubState",
+        )),
+    })
+}
+
+pub fn unit_state_page_from_json(json: &Json) -> Result<UnitStatePage, FleetError> {
+    try!(get_object(json, "unit state page"));
+
+    let mut states = vec![];
+
+    for state_json in try!(get_array(json, "unit state page", "states")).iter() {
+        states.push(try!(unit_state_from_json(state_json)));
+    }
+
+    Ok(UnitStatePage {
+        states: states,
+        next_page_token: get_next_page_token(json),
+    })
+}
+
+pub fn snapshot_from_json(json: &Json) -> Result<Snapshot, FleetError> {
+    let snapshot_obj = try!(get_object(json, "snapshot"));
+    let version = try!(get_version(snapshot_obj, "snapshot"));
+
+    if version > snapshot::VERSION {
+        return Err(FleetError::Snapshot(SnapshotError::UnsupportedVersion(version)));
+    }
+
+    let mut units = vec![];
+
+    for unit_json in try!(get_array(json, "snapshot", "units")).iter() {
+        units.push(try!(unit_from_json(unit_json)));
+    }
+
+    let mut states = vec![];
+
+    for state_json in try!(get_array(json, "snapshot", "states")).iter() {
+        states.push(try!(unit_state_from_json(state_json)));
+    }
+
+    let mut machines = vec![];
+
+    for machine_json in try!(get_array(json, "snapshot", "machines")).iter() {
+        machines.push(try!(machine_from_json(machine_json)));
+    }
+
+    Ok(Snapshot {
+        version: version,
+        units: units,
+        states: states,
+        machines: machines,
+    })
+}
+
+fn string(value: &str) -> Json {
+    Json::String(value.to_string())
+}
+
+fn insert_optional_string(
+    object: &mut BTreeMap<String, Json>,
+    key: &str,
+    value: &Option<String>,
+) {
+    if let Some(ref value) = *value {
+        object.insert(key.to_string(), string(value));
+    }
+}
+
+pub fn machine_to_json(machine: &Machine) -> Json {
+    let mut object = BTreeMap::new();
+    object.insert("id".to_string(), string(&machine.id));
+    object.insert("primaryIP".to_string(), string(&machine.primary_ip));
+
+    if !machine.metadata.is_empty() {
+        let metadata = machine.metadata.iter().map(|(key, value)| {
+            (key.clone(), string(value))
+        }).collect();
+
+        object.insert("metadata".to_string(), Json::Object(metadata));
+    }
+
+    Json::Object(object)
+}
+
+pub fn unit_to_json(unit: &Unit) -> Json {
+    let options = unit.options.iter().map(|option| {
+        let mut object = BTreeMap::new();
+        object.insert("name".to_string(), string(&option.name));
+        object.insert("section".to_string(), string(&option.section));
+        object.insert("value".to_string(), string(&option.value));
+
+        Json::Object(object)
+    }).collect();
+
+    let mut object = BTreeMap::new();
+    object.insert("currentState".to_string(), unit.current_state.to_json());
+    object.insert("desiredState".to_string(), unit.desired_state.to_json());
+    object.insert("name".to_string(), string(&unit.name));
+    object.insert("options".to_string(), Json::Array(options));
+    insert_optional_string(&mut object, "machineID", &unit.machine_id);
+
+    Json::Object(object)
+}
+
+pub fn unit_state_to_json(state: &UnitState) -> Json {
+    let mut object = BTreeMap::new();
+    object.insert("hash".to_string(), string(&state.hash));
+    object.insert("name".to_string(), string(&state.name));
+    object.insert("systemdActiveState".to_string(), string(&state.systemd_active_state));
+    object.insert("systemdLoadState".to_string(), string(&state.systemd_load_state));
+    object.insert("systemdSubState".to_string(), string(&state.systemd_sub_state));
+    insert_optional_string(&mut object, "machineID", &state.machine_id);
+
+    Json::Object(object)
+}
+
+pub fn snapshot_to_json(snapshot: &Snapshot) -> Json {
+    let mut object = BTreeMap::new();
+    object.insert("version".to_string(), Json::U64(snapshot.version));
+    object.insert(
+        "units".to_string(),
+        Json::Array(snapshot.units.iter().map(unit_to_json).collect())
+    );
+    object.insert(
+        "states".to_string(),
+        Json::Array(snapshot.states.iter().map(unit_state_to_json).collect())
+    );
+    object.insert(
+        "machines".to_string(),
+        Json::Array(snapshot.machines.iter().map(machine_to_json).collect())
+    );
+
+    Json::Object(object)
+}
+
+#[cfg(test)]
+mod serialize_tests {
+    use rustc_serialize::json::Json;
+
+    use error::FleetError;
+    use super::{machine_page_from_json, unit_from_json, unit_state_from_json};
+
+    fn decode_error_message(error: FleetError) -> String {
+        match error {
+            FleetError::Decode(error) => format!("{}", error),
+            _ => panic!("expected a decode error"),
+        }
+    }
+
+    #[test]
+    fn it_decodes_a_unit() {
+        let json = Json::from_str(r#"{
+            "currentState": "launched",
+            "desiredState": "launched",
+            "machineID": "abc123",
+            "name": "example.service",
+            "options": [{"name": "ExecStart", "section": "Service", "value": "/bin/true"}]
+        }"#).unwrap();
+
+        let unit = unit_from_json(&json).ok().unwrap();
+
+        assert_eq!(unit.name, "example.service");
+        assert_eq!(unit.machine_id, Some("abc123".to_string()));
+        assert_eq!(unit.options[0].value, "/bin/true");
+    }
+
+    #[test]
+    fn it_fails_on_a_missing_field() {
+        let json = Json::from_str(r#"{"desiredState": "launched", "name": "a.service"}"#).unwrap();
+
+        let message = decode_error_message(unit_from_json(&json).err().unwrap());
+
+        assert_eq!(message, "unit field `currentState` is missing");
+    }
+
+    #[test]
+    fn it_fails_on_an_unknown_unit_state() {
+        let json = Json::from_str(r#"{
+            "currentState": "exploded",
+            "desiredState": "launched",
+            "name": "a.service"
+        }"#).unwrap();
+
+        let message = decode_error_message(unit_from_json(&json).err().unwrap());
+
+        assert_eq!(message, r#"unit field `currentState` is not a valid unit state: "exploded""#);
+    }
+
+    #[test]
+    fn it_fails_on_a_field_of_the_wrong_type() {
+        let json = Json::from_str(r#"{
+            "name": "a.service",
+            "hash": 12345,
+            "systemdLoadState": "loaded",
+            "systemdActiveState": "active",
+            "systemdSubState": "running"
+        }"#).unwrap();
+
+        let message = decode_error_message(unit_state_from_json(&json).err().unwrap());
+
+        assert_eq!(message, "unit state field `hash` is not a string");
+    }
+
+    #[test]
+    fn it_fails_on_invalid_metadata() {
+        let json = Json::from_str(r#"{
+            "machines": [{"id": "abc123", "primaryIP": "1.2.3.4", "metadata": {"region": 1}}]
+        }"#).unwrap();
+
+        let message = decode_error_message(machine_page_from_json(&json).err().unwrap());
+
+        assert_eq!(message, "machine field `metadata.region` is not a string");
+    }
+
+    #[test]
+    fn it_fails_on_a_non_object_page() {
+        let json = Json::from_str("[]").unwrap();
+
+        let message = decode_error_message(machine_page_from_json(&json).err().unwrap());
+
+        assert_eq!(message, "machine page is not a JSON object");
     }
 }
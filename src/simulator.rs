@@ -0,0 +1,429 @@
+use std::fmt;
+
+use glob;
+use schema::{FleetScheduling, Machine, UnitName};
+
+/// A unit already scheduled to a machine, as considered by `simulate`. A global unit appears once
+/// for each machine it is scheduled to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScheduledUnit {
+    /// The name of the unit.
+    pub name: String,
+    /// The ID of the machine the unit is scheduled to.
+    pub machine_id: String,
+    /// The unit's own `Conflicts` patterns, from its `[X-Fleet]` section.
+    pub conflicts: Vec<String>,
+}
+
+/// A machine a unit cannot be scheduled to, and why.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Exclusion {
+    /// The ID of the machine.
+    pub machine_id: String,
+    /// The first requirement the machine does not meet, in words.
+    pub reason: String,
+}
+
+/// Where fleet could schedule a unit, returned by `simulate` and `Client::simulate_scheduling`.
+///
+/// The `Display` output states which machines the unit can be scheduled to, followed by each
+/// excluded machine and the reason it was excluded.
+#[derive(Clone, Debug)]
+pub struct Simulation {
+    /// The name of the unit.
+    pub name: String,
+    /// Whether the unit is global, i.e. would be scheduled to every eligible machine rather than
+    /// one of them.
+    pub global: bool,
+    /// The IDs of the machines the unit can be scheduled to, in the order the machines were given.
+    pub eligible: Vec<String>,
+    /// The machines the unit cannot be scheduled to.
+    pub excluded: Vec<Exclusion>,
+}
+
+impl Simulation {
+    /// Returns whether the unit can be scheduled to at least one machine.
+    pub fn is_schedulable(&self) -> bool {
+        !self.eligible.is_empty()
+    }
+}
+
+impl fmt::Display for Simulation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.eligible.is_empty() {
+            try!(write!(f, "{} cannot be scheduled to any machine", self.name));
+
+            if self.excluded.is_empty() {
+                return writeln!(f, ": the cluster has no machines");
+            }
+
+            try!(writeln!(f, ":"));
+        } else if self.global {
+            try!(writeln!(
+                f,
+                "{} would be scheduled to every one of these machines: {}",
+                self.name,
+                self.eligible.join(", ")
+            ));
+        } else {
+            try!(writeln!(
+                f,
+                "{} can be scheduled to any of these machines: {}",
+                self.name,
+                self.eligible.join(", ")
+            ));
+        }
+
+        for exclusion in self.excluded.iter() {
+            try!(writeln!(f, "  {}: {}", exclusion.machine_id, exclusion.reason));
+        }
+
+        Ok(())
+    }
+}
+
+/// Works out which of `machines` fleet could schedule the unit `name` to, given its scheduling
+/// requirements and the units already `scheduled` to the machines.
+///
+/// The rules are the ones fleet applies:
+///
+/// * `MachineID` matches a machine's full ID or its first 8 characters.
+/// * `MachineMetadata` requires every key to be present with one of the values given for it.
+/// * `MachineOf` requires every named unit to be scheduled to the machine.
+/// * `Conflicts` excludes machines running a unit, other than this one, matching any pattern.
+///   Conflicts go both ways: a machine is also excluded if a unit scheduled to it has a
+///   `Conflicts` pattern matching this unit.
+/// * `Global` makes the unit run on every eligible machine instead of one.
+///
+/// `%n`, `%N`, `%p` and `%i` in `MachineOf` and `Conflicts` values are replaced with the full
+/// name, name without type, prefix and instance of the unit that declares them, as fleet does for
+/// template instances. `Replaces` moves other units away and never excludes a machine.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use fleet::{simulate, FleetScheduling, Machine, UnitName};
+///
+/// let mut metadata = HashMap::new();
+/// metadata.insert("region".to_string(), "us-west-1".to_string());
+///
+/// let machines = vec![
+///     Machine {
+///         id: "abc123".to_string(),
+///         metadata: metadata,
+///         primary_ip: "10.0.0.1".to_string(),
+///     },
+/// ];
+///
+/// let name = UnitName::parse("web.service").unwrap();
+/// let scheduling = FleetScheduling::new().machine_metadata("region", "us-east-1");
+///
+/// let simulation = simulate(&name, &scheduling, &machines, &[]);
+///
+/// assert!(!simulation.is_schedulable());
+/// assert_eq!(
+///     simulation.excluded[0].reason,
+///     "MachineMetadata requires region=us-east-1, but the machine has region=us-west-1"
+/// );
+/// ```
+pub fn simulate(
+    name: &UnitName,
+    scheduling: &FleetScheduling,
+    machines: &[Machine],
+    scheduled: &[ScheduledUnit],
+) -> Simulation {
+    let unit_name = name.to_string();
+    let machine_of: Vec<String> = scheduling.machine_of.iter().map(|unit| {
+        expand(unit, name)
+    }).collect();
+    let conflicts: Vec<String> = scheduling.conflicts.iter().map(|pattern| {
+        expand(pattern, name)
+    }).collect();
+
+    let mut eligible = vec![];
+    let mut excluded = vec![];
+
+    for machine in machines.iter() {
+        let others: Vec<&ScheduledUnit> = scheduled.iter().filter(|unit| {
+            unit.machine_id == machine.id
+        }).collect();
+        let running: Vec<&str> = others.iter().map(|unit| &unit.name[..]).collect();
+
+        let reason = check_machine_id(scheduling, machine)
+            .or_else(|| check_metadata(scheduling, machine))
+            .or_else(|| check_machine_of(&machine_of, &running))
+            .or_else(|| check_conflicts(&conflicts, &running, &unit_name))
+            .or_else(|| check_conflicted_by(&others, &unit_name));
+
+        match reason {
+            Some(reason) => excluded.push(Exclusion {
+                machine_id: machine.id.clone(),
+                reason: reason,
+            }),
+            None => eligible.push(machine.id.clone()),
+        }
+    }
+
+    Simulation {
+        name: unit_name,
+        global: scheduling.global,
+        eligible: eligible,
+        excluded: excluded,
+    }
+}
+
+fn check_machine_id(scheduling: &FleetScheduling, machine: &Machine) -> Option<String> {
+    let machine_id = match scheduling.machine_id {
+        Some(ref machine_id) => machine_id,
+        None => return None,
+    };
+
+    let short_id = if machine.id.len() > 8 { &machine.id[..8] } else { &machine.id[..] };
+
+    if *machine_id == machine.id || machine_id == short_id {
+        None
+    } else {
+        Some(format!("MachineID requires machine {}", machine_id))
+    }
+}
+
+fn check_metadata(scheduling: &FleetScheduling, machine: &Machine) -> Option<String> {
+    let mut keys: Vec<&str> = vec![];
+
+    for &(ref key, _) in scheduling.machine_metadata.iter() {
+        if !keys.contains(&&key[..]) {
+            keys.push(key);
+        }
+    }
+
+    for key in keys {
+        let values: Vec<&str> = scheduling.machine_metadata.iter().filter(|&&(ref other, _)| {
+            other == key
+        }).map(|&(_, ref value)| &value[..]).collect();
+
+        let actual = machine.metadata.get(key);
+
+        if actual.map_or(false, |actual| values.contains(&&actual[..])) {
+            continue;
+        }
+
+        let has = match actual {
+            Some(actual) => format!("{}={}", key, actual),
+            None => format!("no {}", key),
+        };
+
+        return Some(format!(
+            "MachineMetadata requires {}={}, but the machine has {}",
+            key,
+            values.join("|"),
+            has
+        ));
+    }
+
+    None
+}
+
+fn check_machine_of(machine_of: &[String], running: &[&str]) -> Option<String> {
+    machine_of.iter().find(|unit| !running.contains(&&unit[..])).map(|unit| {
+        format!("MachineOf requires {}, which is not scheduled to the machine", unit)
+    })
+}
+
+fn check_conflicts(conflicts: &[String], running: &[&str], unit_name: &str) -> Option<String> {
+    for pattern in conflicts.iter() {
+        let conflicting = running.iter().find(|other| {
+            **other != unit_name && glob::matches(pattern, other)
+        });
+
+        if let Some(other) = conflicting {
+            return Some(format!(
+                "Conflicts value {} matches {}, which is scheduled to the machine",
+                pattern,
+                other
+            ));
+        }
+    }
+
+    None
+}
+
+fn check_conflicted_by(others: &[&ScheduledUnit], unit_name: &str) -> Option<String> {
+    for other in others.iter().filter(|other| other.name != unit_name) {
+        let name = UnitName::parse(&other.name).ok();
+
+        for pattern in other.conflicts.iter() {
+            let pattern = match name {
+                Some(ref name) => expand(pattern, name),
+                None => pattern.clone(),
+            };
+
+            if glob::matches(&pattern, unit_name) {
+                return Some(format!(
+                    "{}, which is scheduled to the machine, has Conflicts value {}, which matches \
+                     the unit",
+                    other.name,
+                    pattern
+                ));
+            }
+        }
+    }
+
+    None
+}
+
+// Replaces the specifiers fleet supports in scheduling options with parts of the unit's name.
+fn expand(value: &str, name: &UnitName) -> String {
+    let full_name = name.to_string();
+    let without_type = &full_name[..full_name.len() - name.unit_type().len() - 1];
+
+    value
+        .replace("%n", &full_name)
+        .replace("%N", without_type)
+        .replace("%p", name.prefix())
+        .replace("%i", name.instance().unwrap_or(""))
+}
+
+#[cfg(test)]
+mod simulator_tests {
+    use std::collections::HashMap;
+
+    use super::{simulate, ScheduledUnit};
+    use schema::{FleetScheduling, Machine, UnitName};
+
+    fn machine(id: &str, region: &str) -> Machine {
+        let mut metadata = HashMap::new();
+        metadata.insert("region".to_string(), region.to_string());
+
+        Machine {
+            id: id.to_string(),
+            metadata: metadata,
+            primary_ip: "10.0.0.1".to_string(),
+        }
+    }
+
+    fn scheduled(name: &str, machine_id: &str, conflicts: &[&str]) -> ScheduledUnit {
+        ScheduledUnit {
+            name: name.to_string(),
+            machine_id: machine_id.to_string(),
+            conflicts: conflicts.iter().map(|pattern| pattern.to_string()).collect(),
+        }
+    }
+
+    fn machines() -> Vec<Machine> {
+        vec![
+            machine("0123456789abcdef", "us-east-1"),
+            machine("fedcba9876543210", "us-west-1"),
+            machine("aaaaaaaaaaaaaaaa", "eu-west-1"),
+        ]
+    }
+
+    fn name(name: &str) -> UnitName {
+        UnitName::parse(name).unwrap()
+    }
+
+    #[test]
+    fn it_allows_every_machine_without_requirements() {
+        let simulation = simulate(&name("web.service"), &FleetScheduling::new(), &machines(), &[]);
+
+        assert_eq!(simulation.eligible.len(), 3);
+        assert!(simulation.excluded.is_empty());
+    }
+
+    #[test]
+    fn it_matches_machine_ids_and_metadata() {
+        let scheduling = FleetScheduling::new().machine_id("01234567");
+        let simulation = simulate(&name("web.service"), &scheduling, &machines(), &[]);
+
+        assert_eq!(simulation.eligible, vec!["0123456789abcdef".to_string()]);
+        assert_eq!(simulation.excluded[0].reason, "MachineID requires machine 01234567");
+
+        let scheduling = FleetScheduling::new()
+            .machine_metadata("region", "us-east-1")
+            .machine_metadata("region", "us-west-1");
+        let simulation = simulate(&name("web.service"), &scheduling, &machines(), &[]);
+
+        assert_eq!(simulation.eligible.len(), 2);
+        assert_eq!(
+            simulation.excluded[0].reason,
+            "MachineMetadata requires region=us-east-1|us-west-1, but the machine has \
+             region=eu-west-1"
+        );
+    }
+
+    #[test]
+    fn it_follows_machine_of_and_avoids_conflicts() {
+        let scheduled = vec![
+            scheduled("db.service", "fedcba9876543210", &[]),
+            scheduled("web@1.service", "0123456789abcdef", &[]),
+        ];
+
+        let scheduling = FleetScheduling::new().machine_of("db.service");
+        let simulation = simulate(&name("web.service"), &scheduling, &machines(), &scheduled);
+
+        assert_eq!(simulation.eligible, vec!["fedcba9876543210".to_string()]);
+
+        let scheduling = FleetScheduling::new().conflicts("%p@*.service");
+        let simulation = simulate(&name("web@2.service"), &scheduling, &machines(), &scheduled);
+
+        assert_eq!(simulation.eligible.len(), 2);
+        assert_eq!(
+            simulation.excluded[0].reason,
+            "Conflicts value web@*.service matches web@1.service, which is scheduled to the \
+             machine"
+        );
+
+        let simulation = simulate(&name("web@1.service"), &scheduling, &machines(), &scheduled);
+
+        assert_eq!(simulation.eligible.len(), 3);
+    }
+
+    #[test]
+    fn it_avoids_machines_running_units_that_conflict_with_it() {
+        let scheduled = vec![
+            scheduled("web@1.service", "0123456789abcdef", &["%p@*.service"]),
+            scheduled("db.service", "fedcba9876543210", &["cache.service"]),
+        ];
+
+        let scheduling = FleetScheduling::new();
+        let simulation = simulate(&name("web@2.service"), &scheduling, &machines(), &scheduled);
+
+        assert_eq!(simulation.eligible.len(), 2);
+        assert_eq!(simulation.excluded[0].machine_id, "0123456789abcdef");
+        assert_eq!(
+            simulation.excluded[0].reason,
+            "web@1.service, which is scheduled to the machine, has Conflicts value web@*.service, \
+             which matches the unit"
+        );
+
+        let simulation = simulate(&name("cache.service"), &scheduling, &machines(), &scheduled);
+
+        assert_eq!(simulation.eligible.len(), 2);
+        assert_eq!(simulation.excluded[0].machine_id, "fedcba9876543210");
+
+        let simulation = simulate(&name("web@1.service"), &scheduling, &machines(), &scheduled);
+
+        assert_eq!(simulation.eligible.len(), 3);
+    }
+
+    #[test]
+    fn it_explains_why_no_machine_qualifies() {
+        let scheduling = FleetScheduling::new().machine_metadata("disk", "ssd");
+        let simulation = simulate(&name("web.service"), &scheduling, &machines()[..1], &[]);
+
+        assert!(!simulation.is_schedulable());
+        assert_eq!(
+            simulation.to_string(),
+            "web.service cannot be scheduled to any machine:\n  0123456789abcdef: \
+             MachineMetadata requires disk=ssd, but the machine has no disk\n"
+        );
+
+        let simulation = simulate(&name("web.service"), &scheduling, &[], &[]);
+
+        assert_eq!(
+            simulation.to_string(),
+            "web.service cannot be scheduled to any machine: the cluster has no machines\n"
+        );
+    }
+}
@@ -0,0 +1,295 @@
+//! Exporting everything fleet knows about a cluster and restoring its units later, e.g. for
+//! disaster recovery.
+//!
+//! A `Snapshot` holds every unit with its options and desired state, along with the unit states
+//! and machines at the time it was taken. Only the units can be restored: unit states and
+//! machines are reported by the machines themselves and are kept for reference.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use std::io::{Read, Write};
+//!
+//! use fleet::Client;
+//! use fleet::snapshot::{self, Conflict, ImportOptions, Snapshot};
+//!
+//! let client = Client::new("http://localhost:2999").ok().unwrap();
+//!
+//! let snapshot = snapshot::export(&client).ok().unwrap();
+//! let mut file = File::create("fleet.json").unwrap();
+//! file.write_all(snapshot.to_string().as_bytes()).unwrap();
+//!
+//! // Later, against an empty cluster:
+//!
+//! let mut contents = String::new();
+//! File::open("fleet.json").unwrap().read_to_string(&mut contents).unwrap();
+//!
+//! let snapshot = Snapshot::parse(&contents).ok().unwrap();
+//! let options = ImportOptions {
+//!     on_conflict: Conflict::Skip,
+//!     ..ImportOptions::default()
+//! };
+//!
+//! let plan = snapshot::import(&client, &snapshot, &options).ok().unwrap();
+//!
+//! print!("{}", plan);
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use rustc_serialize::json::{Json, ToJson};
+
+use client::Client;
+use error::{FleetError, SnapshotError};
+use reconcile::{DesiredUnit, Plan};
+use schema::{Machine, Unit, UnitState};
+use serialize;
+
+/// The version of the snapshot format written by this version of the crate. Snapshots with a
+/// higher version cannot be read.
+pub const VERSION: u64 = 1;
+
+/// Everything fleet knows about a cluster at one point in time.
+///
+/// A snapshot is stored as a JSON object with a `version` field and `units`, `states` and
+/// `machines` arrays, whose elements have the same format as in the fleet API. Fields added by
+/// later versions of the format are ignored when reading. The `Display` output of a snapshot is
+/// its JSON.
+pub struct Snapshot {
+    /// The version of the format the snapshot was written in.
+    pub version: u64,
+    /// Every unit in the cluster.
+    pub units: Vec<Unit>,
+    /// The states of the units, as reported by the machines they were scheduled to.
+    pub states: Vec<UnitState>,
+    /// Every machine in the cluster.
+    pub machines: Vec<Machine>,
+}
+
+impl Snapshot {
+    /// Reads a snapshot from its JSON.
+    ///
+    /// # Failures
+    ///
+    /// Returns a `FleetError::Snapshot` if the JSON is invalid or was written in a newer version
+    /// of the format.
+    pub fn parse(json: &str) -> Result<Snapshot, FleetError> {
+        let json = try!(Json::from_str(json).map_err(|error| {
+            FleetError::Snapshot(SnapshotError::Invalid(error.to_string()))
+        }));
+
+        serialize::snapshot_from_json(&json).map_err(|error| match error {
+            FleetError::Decode(error) => {
+                FleetError::Snapshot(SnapshotError::Invalid(error.to_string()))
+            },
+            error => error,
+        })
+    }
+}
+
+impl ToJson for Snapshot {
+    fn to_json(&self) -> Json {
+        serialize::snapshot_to_json(self)
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_json().pretty())
+    }
+}
+
+/// What `import` does with a unit in the snapshot that already exists in the cluster.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Conflict {
+    /// Leave the existing unit as it is.
+    Skip,
+    /// Replace the existing unit with the one in the snapshot if their contents differ, or set its
+    /// desired state to the one in the snapshot if only that differs.
+    Replace,
+    /// Return an error before changing anything.
+    Fail,
+}
+
+/// Settings for `import`.
+#[derive(Clone, Debug)]
+pub struct ImportOptions {
+    /// What to do with units that already exist. Defaults to `Conflict::Fail`.
+    pub on_conflict: Conflict,
+    /// If true, the plan is checked and returned without being applied. Defaults to false.
+    pub dry_run: bool,
+}
+
+impl Default for ImportOptions {
+    fn default() -> ImportOptions {
+        ImportOptions {
+            on_conflict: Conflict::Fail,
+            dry_run: false,
+        }
+    }
+}
+
+/// Takes a snapshot of the cluster `client` talks to.
+///
+/// # Failures
+///
+/// Returns the first error that occurs while listing the units, unit states or machines.
+pub fn export(client: &Client) -> Result<Snapshot, FleetError> {
+    Ok(Snapshot {
+        version: VERSION,
+        units: try!(client.units().collect()),
+        states: try!(client.unit_states(None, None).collect()),
+        machines: try!(client.machines().collect()),
+    })
+}
+
+/// Restores the units in `snapshot` to the cluster `client` talks to, returning the plan that
+/// was applied. Units in the cluster that are not in the snapshot are left alone.
+///
+/// # Failures
+///
+/// Returns a `FleetError::Snapshot` if a unit already exists and `options.on_conflict` is
+/// `Conflict::Fail`. Returns a `FleetError::Apply` if restoring a unit fails, in which case the
+/// units before it in the plan remain restored. See `Plan::apply`.
+pub fn import(
+    client: &Client,
+    snapshot: &Snapshot,
+    options: &ImportOptions,
+) -> Result<Plan, FleetError> {
+    let existing: Vec<Unit> = try!(client.units().collect());
+
+    // If a unit appears more than once in the snapshot, the last one wins.
+    let units: BTreeMap<&str, &Unit> = snapshot.units.iter().map(|unit| {
+        (&unit.name[..], unit)
+    }).collect();
+
+    let conflicts: Vec<&Unit> = existing.iter().filter(|unit| {
+        units.contains_key(&unit.name[..])
+    }).collect();
+
+    if options.on_conflict == Conflict::Fail && !conflicts.is_empty() {
+        let mut names: Vec<String> = conflicts.iter().map(|unit| unit.name.clone()).collect();
+        names.sort();
+
+        return Err(FleetError::Snapshot(SnapshotError::Conflict(names)));
+    }
+
+    // Planning against only the existing units that are in the snapshot means no unit is
+    // destroyed. Skipped units are left out on both sides, so they are not changed either.
+    let (desired, current): (Vec<DesiredUnit>, Vec<Unit>) = match options.on_conflict {
+        Conflict::Skip => {
+            let desired = units.values().filter(|unit| {
+                !conflicts.iter().any(|conflict| conflict.name == unit.name)
+            }).map(|unit| desired_unit(unit)).collect();
+
+            (desired, vec![])
+        },
+        Conflict::Replace | Conflict::Fail => {
+            let desired = units.values().map(|unit| desired_unit(unit)).collect();
+            let current = conflicts.into_iter().cloned().collect();
+
+            (desired, current)
+        },
+    };
+
+    let plan = Plan::new(&desired, &current);
+
+    try!(plan.apply(client, options.dry_run));
+
+    Ok(plan)
+}
+
+fn desired_unit(unit: &Unit) -> DesiredUnit {
+    DesiredUnit {
+        name: unit.name.clone(),
+        options: unit.options.clone(),
+        desired_state: unit.desired_state,
+    }
+}
+
+#[cfg(test)]
+mod snapshot_tests {
+    use std::collections::HashMap;
+
+    use super::{Snapshot, VERSION};
+    use error::{FleetError, SnapshotError};
+    use schema::{Machine, Unit, UnitOption, UnitState, UnitStates};
+
+    #[test]
+    fn it_round_trips_through_json() {
+        let mut metadata = HashMap::new();
+        metadata.insert("region".to_string(), "us-east-1".to_string());
+
+        let snapshot = Snapshot {
+            version: VERSION,
+            units: vec![
+                Unit {
+                    current_state: UnitStates::Launched,
+                    desired_state: UnitStates::Launched,
+                    machine_id: Some("abc123".to_string()),
+                    name: "web.service".to_string(),
+                    options: vec![
+                        UnitOption {
+                            name: "ExecStart".to_string(),
+                            section: "Service".to_string(),
+                            value: "/usr/bin/web".to_string(),
+                        },
+                    ],
+                },
+            ],
+            states: vec![
+                UnitState {
+                    name: "web.service".to_string(),
+                    hash: "abc".to_string(),
+                    machine_id: Some("abc123".to_string()),
+                    systemd_load_state: "loaded".to_string(),
+                    systemd_active_state: "active".to_string(),
+                    systemd_sub_state: "running".to_string(),
+                },
+            ],
+            machines: vec![
+                Machine {
+                    id: "abc123".to_string(),
+                    metadata: metadata,
+                    primary_ip: "10.0.0.1".to_string(),
+                },
+            ],
+        };
+
+        let parsed = Snapshot::parse(&snapshot.to_string()).ok().unwrap();
+
+        assert_eq!(parsed.version, VERSION);
+        assert_eq!(parsed.units[0].name, "web.service");
+        assert_eq!(parsed.units[0].options, snapshot.units[0].options);
+        assert_eq!(parsed.states[0].systemd_sub_state, "running");
+        assert_eq!(parsed.machines[0].metadata.get("region").unwrap(), "us-east-1");
+    }
+
+    #[test]
+    fn it_ignores_unknown_fields() {
+        let snapshot = Snapshot::parse(r#"{"version":1,"units":[],"takenAt":"now"}"#);
+
+        assert!(snapshot.ok().unwrap().units.is_empty());
+    }
+
+    #[test]
+    fn it_rejects_newer_versions() {
+        match Snapshot::parse(r#"{"version":2,"units":[]}"#) {
+            Err(FleetError::Snapshot(SnapshotError::UnsupportedVersion(2))) => {},
+            _ => panic!("expected an unsupported version error"),
+        }
+    }
+
+    #[test]
+    fn it_rejects_invalid_snapshots() {
+        let error = Snapshot::parse(r#"{"units":[]}"#).err().unwrap();
+
+        assert_eq!(format!("{}", error), "Invalid snapshot: snapshot field `version` is missing");
+
+        let error = Snapshot::parse("{").err().unwrap();
+
+        assert!(format!("{}", error).starts_with("Invalid snapshot: "));
+    }
+}
@@ -0,0 +1,191 @@
+use std::ffi::CString;
+use std::io;
+use std::net::{IpAddr, TcpStream};
+use std::os::raw::{c_char, c_int, c_uint, c_void};
+use std::path::PathBuf;
+use std::ptr;
+
+use openssl::ssl::error::SslError;
+use openssl::ssl::{Ssl, SslContext, SslMethod, SslStream, SSL_VERIFY_PEER};
+use openssl::x509::{X509, X509FileType};
+
+use error::TlsError;
+
+/// The TLS settings collected by `ClientBuilder` for connections to fleet over HTTPS.
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    pub ca_file: Option<PathBuf>,
+    pub client_certificate: Option<(PathBuf, PathBuf)>,
+    pub server_name: Option<String>,
+    pub verify_hostname: bool,
+}
+
+impl Default for TlsConfig {
+    fn default() -> TlsConfig {
+        TlsConfig {
+            ca_file: None,
+            client_certificate: None,
+            server_name: None,
+            verify_hostname: true,
+        }
+    }
+}
+
+impl TlsConfig {
+    /// Loads the configured certificates and keys, returning a `TlsConnector` that uses them.
+    pub fn connector(&self) -> Result<TlsConnector, TlsError> {
+        let mut context = try!(SslContext::new(SslMethod::Sslv23).map_err(|error| {
+            tls_error(format!("could not initialize OpenSSL: {}", error))
+        }));
+
+        context.set_verify(SSL_VERIFY_PEER, None);
+
+        try!(context.set_default_verify_paths().map_err(|error| {
+            tls_error(format!("could not load the default CA certificates: {}", error))
+        }));
+
+        if let Some(ref path) = self.ca_file {
+            try!(context.set_CA_file(path).map_err(|error| {
+                tls_error(format!("could not load CA file `{}`: {}", path.display(), error))
+            }));
+        }
+
+        if let Some((ref certificate, ref key)) = self.client_certificate {
+            try!(context.set_certificate_chain_file(certificate, X509FileType::PEM).map_err(
+                |error| tls_error(format!(
+                    "could not load client certificate `{}`: {}",
+                    certificate.display(),
+                    error
+                ))
+            ));
+
+            try!(context.set_private_key_file(key, X509FileType::PEM).map_err(|error| {
+                tls_error(format!("could not load client key `{}`: {}", key.display(), error))
+            }));
+
+            try!(context.check_private_key().map_err(|error| {
+                tls_error(format!(
+                    "client key `{}` does not match client certificate `{}`: {}",
+                    key.display(),
+                    certificate.display(),
+                    error
+                ))
+            }));
+        }
+
+        Ok(TlsConnector {
+            context: context,
+            server_name: self.server_name.clone(),
+            verify_hostname: self.verify_hostname,
+        })
+    }
+}
+
+fn tls_error(message: String) -> TlsError {
+    TlsError {
+        message: message,
+    }
+}
+
+fn handshake_error(server_name: &str, message: String) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        tls_error(format!("TLS handshake with `{}` failed: {}", server_name, message)),
+    )
+}
+
+/// Performs the TLS handshake on connections to fleet over HTTPS.
+pub struct TlsConnector {
+    context: SslContext,
+    server_name: Option<String>,
+    verify_hostname: bool,
+}
+
+impl TlsConnector {
+    /// Performs the TLS handshake on `stream`, a connection to `host`. The certificate fleet
+    /// presents must be valid for the configured server name if there is one, and for `host`
+    /// otherwise.
+    pub fn connect(&self, host: &str, stream: TcpStream) -> io::Result<SslStream<TcpStream>> {
+        let server_name = match self.server_name {
+            Some(ref server_name) => &server_name[..],
+            None => host,
+        };
+
+        let ssl = try!(Ssl::new(&self.context).map_err(|error| {
+            handshake_error(server_name, error.to_string())
+        }));
+
+        // SNI only carries host names, never IP addresses.
+        if server_name.parse::<IpAddr>().is_err() {
+            try!(ssl.set_hostname(server_name).map_err(|error| {
+                handshake_error(server_name, error.to_string())
+            }));
+        }
+
+        let stream = try!(SslStream::new_from(ssl, stream).map_err(|error| match error {
+            // The socket's read or write timeout expired during the handshake.
+            SslError::StreamError(ref error) if is_timeout(error) => {
+                io::Error::new(io::ErrorKind::WouldBlock, "TLS handshake did not finish")
+            },
+            error => handshake_error(server_name, error.to_string()),
+        }));
+
+        if self.verify_hostname {
+            let valid = stream.ssl().peer_certificate().map_or(false, |certificate| {
+                is_valid_for(&certificate, server_name)
+            });
+
+            if !valid {
+                return Err(handshake_error(
+                    server_name,
+                    format!("the certificate is not valid for `{}`", server_name),
+                ));
+            }
+        }
+
+        Ok(stream)
+    }
+}
+
+fn is_timeout(error: &io::Error) -> bool {
+    match error.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => true,
+        _ => false,
+    }
+}
+
+// OpenSSL's own matching of a certificate's subject alternative names, falling back to its common
+// name, against a host name or IP address. The version of the openssl crate hyper uses does not
+// expose these functions or the subject alternative names themselves.
+extern "C" {
+    fn X509_check_host(
+        certificate: *mut c_void,
+        name: *const c_char,
+        length: usize,
+        flags: c_uint,
+        matched: *mut *mut c_char,
+    ) -> c_int;
+
+    fn X509_check_ip_asc(certificate: *mut c_void, address: *const c_char, flags: c_uint) -> c_int;
+}
+
+// Returns whether `certificate` is valid for `name`, which may be a host name, including a
+// wildcard match, or an IP address.
+fn is_valid_for(certificate: &X509, name: &str) -> bool {
+    let handle = certificate.get_handle() as *mut c_void;
+
+    let c_name = match CString::new(name) {
+        Ok(c_name) => c_name,
+        Err(_) => return false,
+    };
+
+    let result = unsafe {
+        if name.parse::<IpAddr>().is_ok() {
+            X509_check_ip_asc(handle, c_name.as_ptr(), 0)
+        } else {
+            X509_check_host(handle, c_name.as_ptr(), name.len(), 0, ptr::null_mut())
+        }
+    };
+
+    result == 1
+}
@@ -0,0 +1,206 @@
+use std::fmt;
+use std::io::{self, Read};
+#[cfg(unix)]
+use std::path::Path;
+
+use hyper::Client as HyperClient;
+use hyper::header::{ContentType, Headers};
+use hyper::net::NetworkConnector;
+
+use auth;
+use connector::{Connector, Deadline, Stream, Target, Timeouts};
+use error::FleetError;
+use pool::{Pool, PoolConfig, PooledStream};
+
+/// The HTTP methods used by the fleet API.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Method {
+    /// An HTTP DELETE request.
+    Delete,
+    /// An HTTP GET request.
+    Get,
+    /// An HTTP PUT request.
+    Put,
+}
+
+/// An HTTP request to the fleet API, as built by `Client`.
+#[derive(Clone)]
+pub struct Request {
+    /// The HTTP method.
+    pub method: Method,
+    /// The full URL of the resource, including any query string.
+    pub url: String,
+    /// Headers to send in addition to `Content-Type`, including any `Authorization` header.
+    pub headers: Vec<(String, String)>,
+    /// The JSON request body, if the request has one.
+    pub body: Option<String>,
+}
+
+impl fmt::Debug for Request {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Request")
+            .field("method", &self.method)
+            .field("url", &self.url)
+            .field("headers", &auth::redact_headers(&self.headers))
+            .field("body", &self.body)
+            .finish()
+    }
+}
+
+/// The response to a `Request`.
+pub struct Response {
+    /// The HTTP status code returned by the server.
+    pub status: u16,
+    /// The full response body.
+    pub body: String,
+}
+
+/// The layer `Client` uses to send HTTP requests to fleet.
+///
+/// `Client` builds each request and interprets each response itself, so a transport only has to
+/// deliver the request and return the status code and body it gets back. Implementing this trait
+/// makes it possible to use a different HTTP stack, to record or modify requests, or to test code
+/// that uses `Client` against an in-process fake instead of a running fleet.
+///
+/// # Failures
+///
+/// A transport should only return an error when no response could be obtained at all. Responses
+/// with error status codes should be returned as `Ok` so that `Client` can extract the error
+/// message fleet provides.
+///
+/// # Examples
+///
+/// ```
+/// use fleet::{Client, FleetError, Method, Request, Response, Transport};
+///
+/// struct NoMachines;
+///
+/// impl Transport for NoMachines {
+///     fn send(&self, request: Request) -> Result<Response, FleetError> {
+///         assert_eq!(request.method, Method::Get);
+///
+///         Ok(Response {
+///             status: 200,
+///             body: "{}".to_string(),
+///         })
+///     }
+/// }
+///
+/// let client = Client::with_transport("http://localhost:2999", NoMachines).ok().unwrap();
+/// let machine_page = client.list_machines(None).ok().unwrap();
+///
+/// assert!(machine_page.machines.is_empty());
+/// ```
+pub trait Transport: Send + Sync {
+    /// Sends a request and returns the server's response.
+    fn send(&self, request: Request) -> Result<Response, FleetError>;
+}
+
+/// The default `Transport`, built on hyper.
+///
+/// All requests go through a single pool of keep-alive connections, so repeated calls reuse open
+/// connections to fleetd instead of opening a new one each time. Requests made from several
+/// threads at once are sent in parallel, each on its own connection. Timeouts can be configured
+/// with `ClientBuilder`; a `HyperTransport` constructed directly never times out.
+pub struct HyperTransport {
+    connector: Connector,
+    pool: Pool<Stream>,
+    timeouts: Timeouts,
+}
+
+impl HyperTransport {
+    /// Constructs a new `HyperTransport` that connects to fleetd over TCP.
+    pub fn new(config: PoolConfig) -> HyperTransport {
+        with_target(config, Target::Tcp(None), Timeouts::default())
+    }
+
+    /// Constructs a new `HyperTransport` that connects to fleetd over the Unix domain socket at
+    /// `path`, regardless of the host in each request's URL. Only available on Unix.
+    #[cfg(unix)]
+    pub fn from_unix_socket<P: AsRef<Path>>(path: P, config: PoolConfig) -> HyperTransport {
+        with_target(config, Target::Unix(path.as_ref().to_path_buf()), Timeouts::default())
+    }
+}
+
+/// Constructs a new `HyperTransport` from the settings collected by `ClientBuilder`.
+pub fn with_target(config: PoolConfig, target: Target, timeouts: Timeouts) -> HyperTransport {
+    HyperTransport {
+        connector: Connector::new(target, timeouts),
+        pool: Pool::new(config),
+        timeouts: timeouts,
+    }
+}
+
+// The `NetworkConnector` for a single request, which takes a connection from the shared pool and
+// applies the request's deadline to it.
+struct RequestConnector<'a> {
+    connector: &'a Connector,
+    pool: &'a Pool<Stream>,
+    deadline: Deadline,
+}
+
+impl<'a> NetworkConnector for RequestConnector<'a> {
+    type Stream = PooledStream<Stream>;
+
+    fn connect(&mut self, host: &str, port: u16, scheme: &str) -> io::Result<PooledStream<Stream>> {
+        let connector = self.connector;
+        let deadline = self.deadline;
+
+        let mut stream = try!(self.pool.connect(host, port, scheme, || {
+            connector.connect(host, port, scheme, deadline)
+        }));
+
+        stream.get_mut().set_deadline(deadline);
+
+        Ok(stream)
+    }
+}
+
+impl Transport for HyperTransport {
+    fn send(&self, request: Request) -> Result<Response, FleetError> {
+        let content_type: ContentType = ContentType("application/json".parse().unwrap());
+        let url = &request.url[..];
+
+        let mut headers = Headers::new();
+
+        headers.set(content_type);
+
+        for &(ref name, ref value) in request.headers.iter() {
+            headers.set_raw(name.clone(), vec![value.clone().into_bytes()]);
+        }
+
+        let mut client = HyperClient::with_connector(RequestConnector {
+            connector: &self.connector,
+            pool: &self.pool,
+            deadline: Deadline::after(self.timeouts.request),
+        });
+
+        let result = {
+            let builder = match request.method {
+                Method::Delete => client.delete(url),
+                Method::Get => client.get(url),
+                Method::Put => client.put(url),
+            }.headers(headers);
+
+            match request.body {
+                Some(ref body) => builder.body(&body[..]).send(),
+                None => builder.send(),
+            }
+        };
+
+        let mut response = match result {
+            Ok(response) => response,
+            Err(error) => return Err(FleetError::from(error)),
+        };
+
+        let mut body = String::new();
+
+        match response.read_to_string(&mut body) {
+            Ok(_) => Ok(Response {
+                status: response.status.to_u16(),
+                body: body,
+            }),
+            Err(error) => Err(FleetError::from(error)),
+        }
+    }
+}
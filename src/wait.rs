@@ -0,0 +1,132 @@
+use std::time::Duration;
+
+use schema::{Unit, UnitState, UnitStates};
+
+/// Settings for `Client::wait_for_unit`.
+///
+/// By default, `Client::wait_for_unit` waits up to a minute for fleet to report the target state
+/// and does not check the states reported by systemd. Setting `active_state` or `sub_state` also
+/// waits until systemd on every machine the unit is scheduled to reports those states.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use fleet::WaitOptions;
+///
+/// // Wait for the unit to be running, for up to two minutes.
+/// let options = WaitOptions {
+///     active_state: Some("active".to_string()),
+///     sub_state: Some("running".to_string()),
+///     timeout: Duration::from_secs(120),
+///     ..WaitOptions::default()
+/// };
+/// ```
+#[derive(Clone, Debug)]
+pub struct WaitOptions {
+    /// The systemd active state, such as "active" or "inactive", to wait for.
+    pub active_state: Option<String>,
+    /// The systemd sub state, such as "running" or "dead", to wait for.
+    pub sub_state: Option<String>,
+    /// How long to wait in total before giving up.
+    pub timeout: Duration,
+    /// The delay between the first and second polls. Each later delay is twice the one before.
+    pub initial_interval: Duration,
+    /// The longest delay between two polls.
+    pub max_interval: Duration,
+}
+
+impl Default for WaitOptions {
+    fn default() -> WaitOptions {
+        WaitOptions {
+            active_state: None,
+            sub_state: None,
+            timeout: Duration::from_secs(60),
+            initial_interval: Duration::from_millis(250),
+            max_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Returns whether `unit`, with the states `states` reported for it, has reached `target` and the
+/// systemd states configured in `options`.
+pub fn converged(
+    options: &WaitOptions,
+    unit: &Unit,
+    states: &[UnitState],
+    target: UnitStates,
+) -> bool {
+    if unit.current_state != target {
+        return false;
+    }
+
+    if options.active_state.is_none() && options.sub_state.is_none() {
+        return true;
+    }
+
+    !states.is_empty() && states.iter().all(|state| matches(options, state))
+}
+
+fn matches(options: &WaitOptions, state: &UnitState) -> bool {
+    let active = options.active_state.as_ref().map_or(true, |active_state| {
+        &state.systemd_active_state == active_state
+    });
+    let sub = options.sub_state.as_ref().map_or(true, |sub_state| {
+        &state.systemd_sub_state == sub_state
+    });
+
+    active && sub
+}
+
+#[cfg(test)]
+mod wait_options_tests {
+    use super::{converged, WaitOptions};
+    use schema::{Unit, UnitState, UnitStates};
+
+    fn unit(current_state: UnitStates) -> Unit {
+        Unit {
+            current_state: current_state,
+            desired_state: UnitStates::Launched,
+            machine_id: Some("abc123".to_string()),
+            name: "test.service".to_string(),
+            options: vec![],
+        }
+    }
+
+    fn state(machine_id: &str, active_state: &str, sub_state: &str) -> UnitState {
+        UnitState {
+            name: "test.service".to_string(),
+            hash: "".to_string(),
+            machine_id: Some(machine_id.to_string()),
+            systemd_load_state: "loaded".to_string(),
+            systemd_active_state: active_state.to_string(),
+            systemd_sub_state: sub_state.to_string(),
+        }
+    }
+
+    #[test]
+    fn it_waits_for_the_target_state() {
+        let options = WaitOptions::default();
+
+        assert!(converged(&options, &unit(UnitStates::Launched), &[], UnitStates::Launched));
+        assert!(!converged(&options, &unit(UnitStates::Loaded), &[], UnitStates::Launched));
+    }
+
+    #[test]
+    fn it_waits_for_systemd_on_every_machine() {
+        let options = WaitOptions {
+            active_state: Some("active".to_string()),
+            sub_state: Some("running".to_string()),
+            ..WaitOptions::default()
+        };
+        let unit = unit(UnitStates::Launched);
+
+        let running = state("abc123", "active", "running");
+        let starting = state("def456", "activating", "start");
+
+        assert!(!converged(&options, &unit, &[], UnitStates::Launched));
+        assert!(!converged(&options, &unit, &[running.clone(), starting], UnitStates::Launched));
+        assert!(converged(&options, &unit, &[running], UnitStates::Launched));
+    }
+}
@@ -0,0 +1,338 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::thread;
+use std::time::Duration;
+
+use client::Client;
+use error::FleetError;
+use schema::{Unit, UnitState};
+
+/// A change in the fleet cluster observed by a `Watcher`.
+#[derive(Clone, Debug)]
+pub enum UnitEvent {
+    /// A unit was created.
+    UnitAdded(Unit),
+    /// A unit was destroyed. Holds the unit as it was last observed.
+    UnitRemoved(Unit),
+    /// The state systemd reports for a unit on a machine changed. `previous` is `None` when the
+    /// unit was not running on the machine before, and `current` is `None` when it no longer is.
+    StateChanged {
+        /// The unit's name.
+        name: String,
+        /// The machine the state is reported by.
+        machine_id: Option<String>,
+        /// The state before the change.
+        previous: Option<UnitState>,
+        /// The state after the change.
+        current: Option<UnitState>,
+    },
+    /// A unit moved to a different machine.
+    Rescheduled {
+        /// The unit, as scheduled now.
+        unit: Unit,
+        /// The machine the unit was scheduled to before.
+        previous_machine_id: Option<String>,
+    },
+    /// The hash of the unit file running on a machine changed.
+    HashChanged {
+        /// The unit's name.
+        name: String,
+        /// The machine the hash is reported by.
+        machine_id: Option<String>,
+        /// The hash before the change.
+        previous_hash: String,
+        /// The hash after the change.
+        current_hash: String,
+    },
+}
+
+/// The units and unit states in the fleet cluster at one point in time.
+#[derive(Default)]
+struct Snapshot {
+    units: BTreeMap<String, Unit>,
+    states: BTreeMap<(String, Option<String>), UnitState>,
+}
+
+impl Snapshot {
+    fn take(client: &Client) -> Result<Snapshot, FleetError> {
+        let mut snapshot = Snapshot::default();
+
+        for unit in client.units() {
+            let unit = try!(unit);
+
+            snapshot.units.insert(unit.name.clone(), unit);
+        }
+
+        for state in client.unit_states(None, None) {
+            let state = try!(state);
+
+            snapshot.states.insert((state.name.clone(), state.machine_id.clone()), state);
+        }
+
+        Ok(snapshot)
+    }
+}
+
+/// An iterator over changes to the units in the fleet cluster, returned by `Client::watch`.
+///
+/// fleet has no way to push changes to clients, so the watcher polls `Client::units` and
+/// `Client::unit_states` and compares each snapshot of the cluster to the one before. The first
+/// poll only records the cluster as it is, so events describe changes made after the watcher
+/// started. Advancing the iterator blocks until the next change is observed.
+///
+/// Every poll but the first waits for the interval, including polls after one that failed. If a
+/// poll fails, the error is yielded in place of the next event and the watcher keeps the snapshot
+/// from before it, so no changes are missed once polling succeeds again. The iterator never ends.
+pub struct Watcher<'a> {
+    client: &'a Client,
+    interval: Duration,
+    polled: bool,
+    snapshot: Option<Snapshot>,
+    events: VecDeque<UnitEvent>,
+}
+
+impl<'a> Watcher<'a> {
+    pub fn new(client: &'a Client, interval: Duration) -> Watcher<'a> {
+        Watcher {
+            client: client,
+            interval: interval,
+            polled: false,
+            snapshot: None,
+            events: VecDeque::new(),
+        }
+    }
+}
+
+impl<'a> Iterator for Watcher<'a> {
+    type Item = Result<UnitEvent, FleetError>;
+
+    fn next(&mut self) -> Option<Result<UnitEvent, FleetError>> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Some(Ok(event));
+            }
+
+            if self.polled {
+                thread::sleep(self.interval);
+            }
+
+            self.polled = true;
+
+            let current = match Snapshot::take(self.client) {
+                Ok(snapshot) => snapshot,
+                Err(error) => return Some(Err(error)),
+            };
+
+            if let Some(ref previous) = self.snapshot {
+                self.events.extend(diff(previous, &current));
+            }
+
+            self.snapshot = Some(current);
+        }
+    }
+}
+
+fn diff(previous: &Snapshot, current: &Snapshot) -> Vec<UnitEvent> {
+    let mut events = vec![];
+
+    for (name, unit) in previous.units.iter() {
+        if !current.units.contains_key(name) {
+            events.push(UnitEvent::UnitRemoved(unit.clone()));
+        }
+    }
+
+    for (name, unit) in current.units.iter() {
+        match previous.units.get(name) {
+            None => events.push(UnitEvent::UnitAdded(unit.clone())),
+            Some(previous_unit) if previous_unit.machine_id != unit.machine_id => {
+                events.push(UnitEvent::Rescheduled {
+                    unit: unit.clone(),
+                    previous_machine_id: previous_unit.machine_id.clone(),
+                });
+            },
+            Some(_) => {},
+        }
+    }
+
+    let mut keys: Vec<&(String, Option<String>)> = previous.states.keys().collect();
+    keys.extend(current.states.keys().filter(|key| !previous.states.contains_key(*key)));
+    keys.sort();
+
+    for key in keys {
+        let previous_state = previous.states.get(key);
+        let current_state = current.states.get(key);
+
+        if let (Some(previous_state), Some(current_state)) = (previous_state, current_state) {
+            if previous_state.hash != current_state.hash {
+                events.push(UnitEvent::HashChanged {
+                    name: key.0.clone(),
+                    machine_id: key.1.clone(),
+                    previous_hash: previous_state.hash.clone(),
+                    current_hash: current_state.hash.clone(),
+                });
+            }
+
+            if same_systemd_state(previous_state, current_state) {
+                continue;
+            }
+        }
+
+        events.push(UnitEvent::StateChanged {
+            name: key.0.clone(),
+            machine_id: key.1.clone(),
+            previous: previous_state.cloned(),
+            current: current_state.cloned(),
+        });
+    }
+
+    events
+}
+
+fn same_systemd_state(a: &UnitState, b: &UnitState) -> bool {
+    a.systemd_load_state == b.systemd_load_state &&
+        a.systemd_active_state == b.systemd_active_state &&
+        a.systemd_sub_state == b.systemd_sub_state
+}
+
+#[cfg(test)]
+mod watcher_tests {
+    use super::{diff, Snapshot, UnitEvent};
+    use schema::{Unit, UnitState, UnitStates};
+
+    fn unit(name: &str, machine_id: &str) -> Unit {
+        Unit {
+            current_state: UnitStates::Launched,
+            desired_state: UnitStates::Launched,
+            machine_id: Some(machine_id.to_string()),
+            name: name.to_string(),
+            options: vec![],
+        }
+    }
+
+    fn state(name: &str, machine_id: &str, hash: &str, active_state: &str) -> UnitState {
+        UnitState {
+            name: name.to_string(),
+            hash: hash.to_string(),
+            machine_id: Some(machine_id.to_string()),
+            systemd_load_state: "loaded".to_string(),
+            systemd_active_state: active_state.to_string(),
+            systemd_sub_state: "running".to_string(),
+        }
+    }
+
+    fn snapshot(units: Vec<Unit>, states: Vec<UnitState>) -> Snapshot {
+        let mut snapshot = Snapshot::default();
+
+        for unit in units {
+            snapshot.units.insert(unit.name.clone(), unit);
+        }
+
+        for state in states {
+            snapshot.states.insert((state.name.clone(), state.machine_id.clone()), state);
+        }
+
+        snapshot
+    }
+
+    #[test]
+    fn it_reports_nothing_when_nothing_changed() {
+        let previous = snapshot(vec![unit("a.service", "m1")], vec![
+            state("a.service", "m1", "abc", "active"),
+        ]);
+        let current = snapshot(vec![unit("a.service", "m1")], vec![
+            state("a.service", "m1", "abc", "active"),
+        ]);
+
+        assert!(diff(&previous, &current).is_empty());
+    }
+
+    #[test]
+    fn it_reports_added_and_removed_units() {
+        let previous = snapshot(vec![unit("a.service", "m1")], vec![]);
+        let current = snapshot(vec![unit("b.service", "m1")], vec![]);
+
+        let events = diff(&previous, &current);
+
+        assert_eq!(events.len(), 2);
+
+        match events[0] {
+            UnitEvent::UnitRemoved(ref unit) => assert_eq!(unit.name, "a.service"),
+            ref event => panic!("unexpected event {:?}", event),
+        }
+
+        match events[1] {
+            UnitEvent::UnitAdded(ref unit) => assert_eq!(unit.name, "b.service"),
+            ref event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn it_reports_rescheduled_units_and_their_states() {
+        let previous = snapshot(vec![unit("a.service", "m1")], vec![
+            state("a.service", "m1", "abc", "active"),
+        ]);
+        let current = snapshot(vec![unit("a.service", "m2")], vec![
+            state("a.service", "m2", "abc", "active"),
+        ]);
+
+        let events = diff(&previous, &current);
+
+        assert_eq!(events.len(), 3);
+
+        match events[0] {
+            UnitEvent::Rescheduled { ref unit, ref previous_machine_id } => {
+                assert_eq!(unit.machine_id, Some("m2".to_string()));
+                assert_eq!(*previous_machine_id, Some("m1".to_string()));
+            },
+            ref event => panic!("unexpected event {:?}", event),
+        }
+
+        match events[1] {
+            UnitEvent::StateChanged { ref machine_id, ref previous, ref current, .. } => {
+                assert_eq!(*machine_id, Some("m1".to_string()));
+                assert!(previous.is_some());
+                assert!(current.is_none());
+            },
+            ref event => panic!("unexpected event {:?}", event),
+        }
+
+        match events[2] {
+            UnitEvent::StateChanged { ref machine_id, ref previous, ref current, .. } => {
+                assert_eq!(*machine_id, Some("m2".to_string()));
+                assert!(previous.is_none());
+                assert!(current.is_some());
+            },
+            ref event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn it_reports_state_and_hash_changes() {
+        let previous = snapshot(vec![unit("a.service", "m1")], vec![
+            state("a.service", "m1", "abc", "active"),
+        ]);
+        let current = snapshot(vec![unit("a.service", "m1")], vec![
+            state("a.service", "m1", "def", "failed"),
+        ]);
+
+        let events = diff(&previous, &current);
+
+        assert_eq!(events.len(), 2);
+
+        match events[0] {
+            UnitEvent::HashChanged { ref previous_hash, ref current_hash, .. } => {
+                assert_eq!(previous_hash, "abc");
+                assert_eq!(current_hash, "def");
+            },
+            ref event => panic!("unexpected event {:?}", event),
+        }
+
+        match events[1] {
+            UnitEvent::StateChanged { ref name, ref previous, ref current, .. } => {
+                assert_eq!(name, "a.service");
+                assert_eq!(previous.as_ref().unwrap().systemd_active_state, "active");
+                assert_eq!(current.as_ref().unwrap().systemd_active_state, "failed");
+            },
+            ref event => panic!("unexpected event {:?}", event),
+        }
+    }
+}
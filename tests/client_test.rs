@@ -3,7 +3,7 @@ extern crate retry;
 
 use retry::retry;
 
-use fleet::{Client, UnitOption, UnitStates};
+use fleet::{ApiErrorKind, Client, UnitOption, UnitStates};
 
 #[test]
 fn unit_lifecycle() {
@@ -83,7 +83,8 @@ fn create_invalid_unit_missing_name() {
     let result = client.create_unit("", UnitStates::Launched, options);
     let error = result.err().unwrap();
 
-    assert_eq!(format!("{}", error), "404: Error in JSON response from Fleet was empty");
+    assert!(error.api_kind().is_none());
+    assert_eq!(format!("{}", error), r#"Invalid unit name "": is empty"#);
 }
 
 #[test]
@@ -94,6 +95,7 @@ fn create_invalid_unit_missing_options() {
     let result = client.create_unit("optionless.service", UnitStates::Launched, options);
     let error = result.err().unwrap();
 
+    assert_eq!(error.api_kind(), Some(ApiErrorKind::Conflict));
     assert_eq!(format!("{}", error), "409: unit does not exist and options field empty");
 }
 
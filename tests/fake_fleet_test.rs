@@ -0,0 +1,283 @@
+#![cfg(feature = "fake")]
+
+extern crate fleet;
+
+use fleet::{snapshot, ApiErrorKind, Conflict, FakeFleet, ImportOptions, Machine, Snapshot};
+use fleet::{FleetScheduling, UnitOption, UnitStates};
+
+fn options() -> Vec<UnitOption> {
+    vec![
+        UnitOption {
+            name: "ExecStart".to_string(),
+            section: "Service".to_string(),
+            value: "/usr/bin/sleep 3000".to_string(),
+        },
+    ]
+}
+
+#[test]
+fn unit_lifecycle() {
+    let fake = FakeFleet::start().unwrap();
+    fake.add_machine("abc123", "10.0.0.1", &[]);
+
+    let client = fake.client();
+
+    // Create unit
+
+    let create_result = client.create_unit("test.service", UnitStates::Launched, options());
+
+    assert!(create_result.is_ok(), "{}", create_result.err().unwrap());
+
+    // Get unit
+
+    let unit = client.get_unit("test.service").ok().unwrap();
+
+    assert_eq!(&unit.name[..], "test.service");
+    assert_eq!(unit.machine_id, Some("abc123".to_string()));
+
+    // Modify unit's desired state
+
+    let modify_result = client.modify_unit("test.service", UnitStates::Loaded);
+
+    assert!(modify_result.is_ok(), "{}", modify_result.err().unwrap());
+
+    // List units
+
+    let unit_pages = client.list_units(None).ok().unwrap();
+
+    assert_eq!(unit_pages.units.len(), 1);
+    assert_eq!(unit_pages.units[0].name, "test.service");
+
+    // List unit states
+
+    let unit_state_pages = client.list_unit_states(None, None, None).ok().unwrap();
+    let unit_state = &unit_state_pages.states[0];
+
+    assert_eq!(unit_state.name, "test.service");
+    assert_eq!(unit_state.machine_id, Some("abc123".to_string()));
+    assert_eq!(unit_state.systemd_load_state, "loaded");
+    assert_eq!(unit_state.systemd_active_state, "inactive");
+    assert_eq!(unit_state.systemd_sub_state, "dead");
+
+    // Destroy unit
+
+    let destroy_result = client.destroy_unit("test.service");
+
+    assert!(destroy_result.is_ok(), "{}", destroy_result.err().unwrap());
+
+    let error = client.get_unit("test.service").err().unwrap();
+
+    assert_eq!(error.api_kind(), Some(ApiErrorKind::NotFound));
+}
+
+#[test]
+fn create_invalid_unit_missing_name() {
+    let fake = FakeFleet::start().unwrap();
+    let client = fake.client();
+
+    let result = client.create_unit("", UnitStates::Launched, options());
+    let error = result.err().unwrap();
+
+    assert!(error.api_kind().is_none());
+    assert_eq!(format!("{}", error), r#"Invalid unit name "": is empty"#);
+}
+
+#[test]
+fn create_invalid_unit_missing_options() {
+    let fake = FakeFleet::start().unwrap();
+    let client = fake.client();
+
+    let result = client.create_unit("optionless.service", UnitStates::Launched, vec![]);
+    let error = result.err().unwrap();
+
+    assert_eq!(error.api_kind(), Some(ApiErrorKind::Conflict));
+    assert_eq!(format!("{}", error), "409: unit does not exist and options field empty");
+}
+
+#[test]
+fn list_machines() {
+    let fake = FakeFleet::start().unwrap();
+    fake.add_machine("abc123", "10.0.0.1", &[("region", "us-east-1")]);
+
+    let client = fake.client();
+    let machines: Vec<Machine> = client.machines().map(|machine| machine.ok().unwrap()).collect();
+
+    assert_eq!(machines.len(), 1);
+    assert_eq!(machines[0].metadata.get("region"), Some(&"us-east-1".to_string()));
+}
+
+#[test]
+fn iterate_over_pages() {
+    let fake = FakeFleet::start().unwrap();
+    fake.add_machine("abc123", "10.0.0.1", &[]);
+    fake.add_machine("def456", "10.0.0.2", &[]);
+    fake.set_page_size(1);
+
+    let client = fake.client();
+
+    for name in ["a.service", "b.service", "c.service"].iter() {
+        client.create_unit(name, UnitStates::Launched, options()).ok().unwrap();
+    }
+
+    let names: Vec<String> = client.units().map(|unit| unit.ok().unwrap().name).collect();
+
+    assert_eq!(names, vec!["a.service", "b.service", "c.service"]);
+
+    let states: Vec<String> = client.unit_states(Some("def456"), None)
+        .map(|state| state.ok().unwrap().name)
+        .collect();
+
+    assert_eq!(states, vec!["b.service"]);
+}
+
+#[test]
+fn export_and_import_a_snapshot() {
+    let source = FakeFleet::start().unwrap();
+    source.add_machine("abc123", "10.0.0.1", &[("region", "us-east-1")]);
+
+    let source_client = source.client();
+
+    source_client.create_unit("a.service", UnitStates::Launched, options()).ok().unwrap();
+    source_client.create_unit("b.service", UnitStates::Inactive, options()).ok().unwrap();
+
+    let snapshot = snapshot::export(&source_client).ok().unwrap();
+
+    assert_eq!(snapshot.units.len(), 2);
+    assert_eq!(snapshot.states.len(), 1);
+    assert_eq!(snapshot.machines.len(), 1);
+
+    let snapshot = Snapshot::parse(&snapshot.to_string()).ok().unwrap();
+
+    let target = FakeFleet::start().unwrap();
+    target.add_machine("def456", "10.0.0.2", &[]);
+
+    let target_client = target.client();
+
+    target_client.create_unit("b.service", UnitStates::Loaded, options()).ok().unwrap();
+    target_client.create_unit("c.service", UnitStates::Loaded, options()).ok().unwrap();
+
+    // Conflicts fail by default, before anything is changed.
+
+    let error = snapshot::import(&target_client, &snapshot, &ImportOptions::default())
+        .err()
+        .unwrap();
+
+    assert_eq!(format!("{}", error), "Units already exist in the cluster: b.service");
+    assert!(target_client.get_unit("a.service").is_err());
+
+    // Skipping conflicts restores only the missing units.
+
+    let options = ImportOptions {
+        on_conflict: Conflict::Skip,
+        ..ImportOptions::default()
+    };
+
+    let plan = snapshot::import(&target_client, &snapshot, &options).ok().unwrap();
+
+    assert_eq!(plan.changes.len(), 1);
+
+    let restored = target_client.get_unit("a.service").ok().unwrap();
+    let skipped = target_client.get_unit("b.service").ok().unwrap();
+
+    assert_eq!(restored.machine_id, Some("def456".to_string()));
+    assert_eq!(skipped.desired_state, UnitStates::Loaded);
+
+    // Replacing conflicts brings the remaining unit in line with the snapshot.
+
+    let options = ImportOptions {
+        on_conflict: Conflict::Replace,
+        ..ImportOptions::default()
+    };
+
+    let plan = snapshot::import(&target_client, &snapshot, &options).ok().unwrap();
+
+    assert_eq!(plan.changes.len(), 1);
+    let replaced = target_client.get_unit("b.service").ok().unwrap();
+
+    assert_eq!(replaced.desired_state, UnitStates::Inactive);
+
+    // Units that are not in the snapshot are left alone.
+
+    assert!(target_client.get_unit("c.service").is_ok());
+}
+
+#[test]
+fn submit_and_list_template_instances() {
+    let fake = FakeFleet::start().unwrap();
+    fake.add_machine("abc123", "10.0.0.1", &[]);
+
+    let client = fake.client();
+
+    client.create_unit("web@.service", UnitStates::Inactive, options()).ok().unwrap();
+    client.create_unit("api@1.service", UnitStates::Launched, options()).ok().unwrap();
+
+    let instances = client.submit_instances("web@.service", &["1", "2"], UnitStates::Launched)
+        .ok()
+        .unwrap();
+
+    assert_eq!(instances[1].to_string(), "web@2.service");
+
+    let names: Vec<String> = client.template_instances("web@.service").ok().unwrap()
+        .into_iter()
+        .map(|unit| unit.name)
+        .collect();
+
+    assert_eq!(names, vec!["web@1.service", "web@2.service"]);
+
+    let error = client.template_instances("web@1.service").err().unwrap();
+
+    assert_eq!(
+        format!("{}", error),
+        r#"Invalid unit name "web@1.service": is not a template, such as web@.service"#
+    );
+}
+
+#[test]
+fn simulate_scheduling() {
+    let fake = FakeFleet::start().unwrap();
+    fake.add_machine("abc123", "10.0.0.1", &[("region", "us-east-1")]);
+    fake.add_machine("def456", "10.0.0.2", &[("region", "us-west-1")]);
+
+    let client = fake.client();
+
+    client.create_unit("web@1.service", UnitStates::Launched, options()).ok().unwrap();
+
+    let running_on = client.get_unit("web@1.service").ok().unwrap().machine_id.unwrap();
+
+    let scheduling = FleetScheduling::new().conflicts("%p@*.service");
+    let simulation = client.simulate_scheduling("web@2.service", &scheduling).ok().unwrap();
+
+    assert_eq!(simulation.eligible.len(), 1);
+    assert!(simulation.eligible[0] != running_on);
+    assert_eq!(simulation.excluded[0].machine_id, running_on);
+
+    let scheduling = scheduling.machine_metadata("region", "eu-west-1");
+    let simulation = client.simulate_scheduling("web@2.service", &scheduling).ok().unwrap();
+
+    assert!(!simulation.is_schedulable());
+    assert!(
+        simulation.to_string().starts_with("web@2.service cannot be scheduled to any machine:")
+    );
+
+    assert!(client.simulate_scheduling("web", &scheduling).is_err());
+
+    // A unit that is already scheduled excludes its machine for units matching its Conflicts.
+
+    let mut db_options = options();
+
+    db_options.push(UnitOption {
+        name: "Conflicts".to_string(),
+        section: "X-Fleet".to_string(),
+        value: "cache.service".to_string(),
+    });
+
+    client.create_unit("db.service", UnitStates::Launched, db_options).ok().unwrap();
+
+    let db_on = client.get_unit("db.service").ok().unwrap().machine_id.unwrap();
+    let simulation = client.simulate_scheduling("cache.service", &FleetScheduling::new())
+        .ok()
+        .unwrap();
+
+    assert_eq!(simulation.excluded.len(), 1);
+    assert_eq!(simulation.excluded[0].machine_id, db_on);
+}